@@ -0,0 +1,185 @@
+use std::fmt::Display;
+
+use crate::dotnet_regions::GcReservation;
+use crate::pmap::*;
+
+// lowest address a process can map (default of vm.mmap_min_addr)
+const MMAP_MIN_ADDRESS: u64 = 0x10000;
+// start of the kernel half of the address space, the vsyscall page is mapped there
+const KERNEL_ADDRESS_SPACE_START: u64 = 0xffff800000000000;
+// gaps below this size can only be used by small mappings
+const SMALL_GAP_IN_KIBIBYTE: u64 = 1024;
+// warn if less than this share (in percent) of the address space is left
+const LOW_FREE_ADDRESS_SPACE_IN_PERCENT: f64 = 10.0;
+
+/// Unmapped address range between two memory pages
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AddressSpaceGap {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl AddressSpaceGap {
+    pub fn size_in_kibibyte(&self) -> u64 {
+        (self.end - self.start) / 1024
+    }
+}
+
+/// Usage and fragmentation of the user address space
+#[derive(Debug, PartialEq)]
+pub struct AddressSpaceAnalysis {
+    // end of the user address space (32, 47 or 56 bit)
+    pub end: u64,
+    // accessible memory pages
+    pub mapped_in_kibibyte: u64,
+    // memory pages without any permission (reserved address ranges)
+    pub reserved_in_kibibyte: u64,
+    // ordered by address, including the range below the first and above the last memory page
+    pub gaps: Vec<AddressSpaceGap>,
+    // size of the biggest reservation (the GC's if found), which might have to be done again
+    pub largest_reservation_in_kibibyte: u64,
+    pub gc_reservation_found: bool,
+    // `ulimit -v` of the process
+    pub virtual_memory_limit_in_kibibyte: Option<u64>,
+}
+
+impl AddressSpaceAnalysis {
+    pub fn analyze(memory_pages: &PMapVec, virtual_memory_limit_in_kibibyte: Option<u64>) -> Self {
+        let index = memory_pages.index();
+        let pages = index.range(0, KERNEL_ADDRESS_SPACE_START);
+        let highest = pages.last().map(|page| page.end_address()).unwrap_or(0);
+        let end = if highest <= 1 << 32 {
+            1 << 32
+        } else if highest <= 1 << 47 {
+            1 << 47
+        } else {
+            1 << 56
+        };
+
+        let mut gaps = Vec::new();
+        let mut previous_end = MMAP_MIN_ADDRESS;
+        for page in pages {
+            if page.address > previous_end {
+                gaps.push(AddressSpaceGap { start: previous_end, end: page.address });
+            }
+            previous_end = previous_end.max(page.end_address());
+        }
+        if end > previous_end {
+            gaps.push(AddressSpaceGap { start: previous_end, end });
+        }
+
+        let (reserved, mapped): (Vec<&PMap>, Vec<&PMap>) = pages.iter().partition(|page| !page.permissions.intersects(Permissions::Read | Permissions::Write | Permissions::Execute));
+        let gc_reservation = GcReservation::find(memory_pages);
+        AddressSpaceAnalysis {
+            end,
+            mapped_in_kibibyte: mapped.iter().map(|page| page.size_in_kibibyte).sum(),
+            reserved_in_kibibyte: reserved.iter().map(|page| page.size_in_kibibyte).sum(),
+            gaps,
+            largest_reservation_in_kibibyte: gc_reservation
+                .map(|reservation| reservation.size_in_kibibyte())
+                .unwrap_or_else(|| reserved.iter().map(|page| page.size_in_kibibyte).max().unwrap_or(0)),
+            gc_reservation_found: gc_reservation.is_some(),
+            virtual_memory_limit_in_kibibyte,
+        }
+    }
+
+    pub fn size_in_kibibyte(&self) -> u64 {
+        (self.end - MMAP_MIN_ADDRESS) / 1024
+    }
+
+    pub fn free_in_kibibyte(&self) -> u64 {
+        self.gaps.iter().map(|gap| gap.size_in_kibibyte()).sum()
+    }
+
+    pub fn largest_gap(&self) -> Option<&AddressSpaceGap> {
+        self.gaps.iter().max_by_key(|gap| gap.size_in_kibibyte())
+    }
+
+    /// Number and total size of the gaps smaller than 1 MiB
+    pub fn small_gaps(&self) -> (usize, u64) {
+        let small = self.gaps.iter().filter(|gap| gap.size_in_kibibyte() < SMALL_GAP_IN_KIBIBYTE);
+        small.fold((0, 0), |(count, size), gap| (count + 1, size + gap.size_in_kibibyte()))
+    }
+
+    /// Address space, that can still be mapped, limited by the largest gap and the virtual memory limit
+    pub fn available_for_reservation_in_kibibyte(&self) -> u64 {
+        let largest_gap = self.largest_gap().map(|gap| gap.size_in_kibibyte()).unwrap_or(0);
+        match self.virtual_memory_limit_in_kibibyte {
+            Some(limit) => largest_gap.min(limit.saturating_sub(self.mapped_in_kibibyte + self.reserved_in_kibibyte)),
+            None => largest_gap,
+        }
+    }
+
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let reservation = if self.gc_reservation_found { "the GC reservation" } else { "the largest reservation" };
+        if self.largest_reservation_in_kibibyte > self.available_for_reservation_in_kibibyte() {
+            warnings.push(format!(
+                "A reservation as big as {} ({} KiB) would fail, only {} KiB are available in one piece (e.g. a second GC heap or a restart of the runtime in the same process)",
+                reservation,
+                self.largest_reservation_in_kibibyte,
+                self.available_for_reservation_in_kibibyte()));
+        }
+        let limit = self.virtual_memory_limit_in_kibibyte.unwrap_or(self.size_in_kibibyte()).min(self.size_in_kibibyte());
+        let used = self.mapped_in_kibibyte + self.reserved_in_kibibyte;
+        if 100.0 * limit.saturating_sub(used) as f64 / (limit as f64) < LOW_FREE_ADDRESS_SPACE_IN_PERCENT {
+            warnings.push(format!("Less than {} % of the address space ({} KiB) is left, further allocations may fail", LOW_FREE_ADDRESS_SPACE_IN_PERCENT, limit));
+        }
+        if self.end == 1 << 32 && self.gc_reservation_found {
+            warnings.push("32-bit address space: limit the GC reservation with DOTNET_GCRegionRange or DOTNET_GCHeapHardLimit".to_string());
+        }
+        warnings
+    }
+}
+
+impl Display for AddressSpaceAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percent = |size: u64| 100.0 * size as f64 / self.size_in_kibibyte() as f64;
+        writeln!(f, "User Address Space: 0x{:x} - 0x{:x} ({} KiB, {} bit)", MMAP_MIN_ADDRESS, self.end, self.size_in_kibibyte(), self.end.trailing_zeros())?;
+        writeln!(f, "Mapped: {} KiB ({:.3} %)", self.mapped_in_kibibyte, percent(self.mapped_in_kibibyte))?;
+        writeln!(f, "Reserved (no access): {} KiB ({:.3} %)", self.reserved_in_kibibyte, percent(self.reserved_in_kibibyte))?;
+        writeln!(f, "Free: {} KiB ({:.3} %)", self.free_in_kibibyte(), percent(self.free_in_kibibyte()))?;
+        if let Some(limit) = self.virtual_memory_limit_in_kibibyte {
+            writeln!(f, "Virtual Memory Limit: {} KiB (left: {} KiB)", limit, limit.saturating_sub(self.mapped_in_kibibyte + self.reserved_in_kibibyte))?;
+        }
+        if let Some(gap) = self.largest_gap() {
+            writeln!(f, "Largest Free Gap: 0x{:x} - 0x{:x} ({} KiB)", gap.start, gap.end, gap.size_in_kibibyte())?;
+        }
+        let (small_gaps, small_gaps_size) = self.small_gaps();
+        writeln!(f, "Free Gaps: {} (smaller than {} KiB: {} with {} KiB)", self.gaps.len(), SMALL_GAP_IN_KIBIBYTE, small_gaps, small_gaps_size)?;
+        for warning in self.warnings() {
+            writeln!(f, "Warning: {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod address_space_tests {
+    use super::*;
+
+    #[test]
+    fn gaps_and_reservations_of_32_bit_process() {
+        let pages = PMapVec(vec![
+            PMap::test_page(0x10000, "r-xp", 64, MappingKind::AnonymousPrivate(None)),
+            PMap::test_page(0x21000, "rw-p", 4, MappingKind::AnonymousPrivate(None)),
+            PMap::test_page(0x100000, "---p", 1024 * 1024, MappingKind::AnonymousPrivate(None)),
+            PMap::test_page(0x40100000, "rw-p", 1024, MappingKind::AnonymousPrivate(None)),
+            PMap::test_page(0xffff0000, "r--p", 60, MappingKind::AnonymousPrivate(None)),
+            PMap::test_page(0xffffffffff600000, "--xp", 4, MappingKind::AnonymousPrivate(None)),
+        ]);
+
+        let analysis = AddressSpaceAnalysis::analyze(&pages, None);
+        assert_eq!(analysis.end, 1 << 32);
+        assert_eq!((analysis.mapped_in_kibibyte, analysis.reserved_in_kibibyte), (1152, 1024 * 1024));
+        assert_eq!(analysis.gaps.len(), 4);
+        assert_eq!(analysis.small_gaps(), (3, 4 + 888 + 4));
+        assert_eq!(analysis.largest_gap().unwrap().start, 0x40200000);
+        assert_eq!(analysis.free_in_kibibyte() + analysis.mapped_in_kibibyte + analysis.reserved_in_kibibyte, analysis.size_in_kibibyte());
+        assert!(analysis.warnings().is_empty());
+
+        let analysis = AddressSpaceAnalysis::analyze(&pages, Some(2 * 1024 * 1024));
+        assert_eq!(analysis.available_for_reservation_in_kibibyte(), 2 * 1024 * 1024 - 1152 - 1024 * 1024);
+        assert_eq!(analysis.warnings().len(), 1);
+    }
+}
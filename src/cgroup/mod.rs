@@ -0,0 +1,263 @@
+use std::fmt::Display;
+use std::path::Path;
+
+use crate::pmap::*;
+use crate::reclaimable::is_shared_memory_file;
+
+// cgroup v1 reports "no limit" as the biggest page aligned 64-bit value, everything above this is treated as unlimited
+const UNLIMITED_IN_BYTES: u64 = 1 << 62;
+
+/// Memory counters of a cgroup (v2: `memory.max`, `memory.current`, `memory.stat`, v1: `memory.limit_in_bytes`, `memory.usage_in_bytes`, `memory.stat`)
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CgroupMemory {
+    pub version: u8,
+    pub limit_in_kibibyte: Option<u64>,
+    pub current_in_kibibyte: Option<u64>,
+    pub anonymous_in_kibibyte: Option<u64>,
+    // page cache including shared memory
+    pub file_in_kibibyte: Option<u64>,
+    pub shared_memory_in_kibibyte: Option<u64>,
+    // slab, kernel stacks, page tables, socket buffers, ...
+    pub kernel_in_kibibyte: Option<u64>,
+    pub source: String,
+}
+
+fn parse_bytes(content: &str) -> Option<u64> {
+    content.trim().parse::<u64>().ok().map(|bytes| bytes / 1024)
+}
+
+fn stat_value(stat: &[(String, u64)], key: &str) -> Option<u64> {
+    stat.iter().find(|(name, _)| name == key).map(|(_, bytes)| bytes / 1024)
+}
+
+impl CgroupMemory {
+    /// Reads the memory files of a cgroup folder, e.g. `/sys/fs/cgroup` in the container or a captured copy of it
+    pub fn read(path: &str) -> Result<Self, String> {
+        let folder = Path::new(path);
+        let read = |name: &str| std::fs::read_to_string(folder.join(name)).ok();
+        let cgroup = if let Some(limit) = read("memory.max") {
+            CgroupMemory::parse_v2(&limit, read("memory.current").as_deref(), read("memory.stat").as_deref())
+        } else if let Some(limit) = read("memory.limit_in_bytes") {
+            CgroupMemory::parse_v1(&limit, read("memory.usage_in_bytes").as_deref(), read("memory.stat").as_deref(), read("memory.kmem.usage_in_bytes").as_deref())
+        } else {
+            return Err(format!("Neither memory.max nor memory.limit_in_bytes found in {}", path));
+        };
+        Ok(CgroupMemory { source: path.to_string(), ..cgroup })
+    }
+
+    fn parse_stat(stat: Option<&str>) -> Vec<(String, u64)> {
+        stat.unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let (name, value) = line.split_once(' ')?;
+                Some((name.to_string(), value.trim().parse::<u64>().ok()?))
+            })
+            .collect()
+    }
+
+    pub fn parse_v2(limit: &str, current: Option<&str>, stat: Option<&str>) -> Self {
+        let stat = CgroupMemory::parse_stat(stat);
+        let kernel = stat_value(&stat, "kernel").or_else(|| {
+            // older kernels have no sum of the kernel memory
+            let parts = ["slab", "kernel_stack", "pagetables", "sock", "percpu"]
+                .iter()
+                .filter_map(|key| stat_value(&stat, key))
+                .collect::<Vec<_>>();
+            (!parts.is_empty()).then(|| parts.iter().sum())
+        });
+        CgroupMemory {
+            version: 2,
+            limit_in_kibibyte: parse_bytes(limit),
+            current_in_kibibyte: current.and_then(parse_bytes),
+            anonymous_in_kibibyte: stat_value(&stat, "anon"),
+            file_in_kibibyte: stat_value(&stat, "file"),
+            shared_memory_in_kibibyte: stat_value(&stat, "shmem"),
+            kernel_in_kibibyte: kernel,
+            source: String::new(),
+        }
+    }
+
+    pub fn parse_v1(limit: &str, usage: Option<&str>, stat: Option<&str>, kernel_usage: Option<&str>) -> Self {
+        let stat = CgroupMemory::parse_stat(stat);
+        CgroupMemory {
+            version: 1,
+            limit_in_kibibyte: limit.trim().parse::<u64>().ok().filter(|bytes| *bytes < UNLIMITED_IN_BYTES).map(|bytes| bytes / 1024),
+            current_in_kibibyte: usage.and_then(parse_bytes),
+            anonymous_in_kibibyte: stat_value(&stat, "total_rss").or_else(|| stat_value(&stat, "rss")),
+            file_in_kibibyte: stat_value(&stat, "total_cache").or_else(|| stat_value(&stat, "cache")),
+            shared_memory_in_kibibyte: stat_value(&stat, "total_shmem").or_else(|| stat_value(&stat, "shmem")),
+            kernel_in_kibibyte: kernel_usage.and_then(parse_bytes),
+            source: String::new(),
+        }
+    }
+}
+
+/// Resident memory of the process split like the cgroup counters
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ProcessMemory {
+    pub anonymous_in_kibibyte: u64,
+    pub shared_memory_in_kibibyte: u64,
+    pub file_in_kibibyte: u64,
+}
+
+impl ProcessMemory {
+    pub fn from_memory_pages(memory_pages: &PMapVec) -> Self {
+        let mut memory = ProcessMemory::default();
+        for page in &memory_pages.0 {
+            let resident_set_size = page.resident_set_size_in_kibibyte;
+            if matches!(page.mapping_kind, MappingKind::AnonymousShared(_)) || is_shared_memory_file(page) {
+                memory.shared_memory_in_kibibyte += resident_set_size;
+            } else if matches!(page.mapping_kind, MappingKind::File(_)) {
+                // copy-on-write pages of file mappings are anonymous
+                let anonymous = page.anonymous_in_kibibyte.min(resident_set_size);
+                memory.anonymous_in_kibibyte += anonymous;
+                memory.file_in_kibibyte += resident_set_size - anonymous;
+            } else {
+                memory.anonymous_in_kibibyte += resident_set_size;
+            }
+        }
+        memory
+    }
+
+    pub fn total_in_kibibyte(&self) -> u64 {
+        self.anonymous_in_kibibyte + self.shared_memory_in_kibibyte + self.file_in_kibibyte
+    }
+}
+
+/// Usage of the memory limit (of the container) by the process compared with the counters of its cgroup
+pub struct MemoryLimitAnalysis {
+    pub limit_in_kibibyte: Option<u64>,
+    // where the limit comes from
+    pub limit_source: String,
+    pub process: ProcessMemory,
+    pub cgroup: Option<CgroupMemory>,
+}
+
+impl MemoryLimitAnalysis {
+    /// The given limit (in KiB) wins over the one of the cgroup
+    pub fn analyze(memory_pages: &PMapVec, limit_in_kibibyte: Option<u64>, cgroup: Option<CgroupMemory>) -> Self {
+        let (limit_in_kibibyte, limit_source) = match (limit_in_kibibyte, &cgroup) {
+            (Some(limit), _) => (Some(limit), "--memory-limit".to_string()),
+            (None, Some(cgroup)) => (cgroup.limit_in_kibibyte, format!("cgroup v{} {}", cgroup.version, cgroup.source)),
+            (None, None) => (None, String::new()),
+        };
+        MemoryLimitAnalysis { limit_in_kibibyte, limit_source, process: ProcessMemory::from_memory_pages(memory_pages), cgroup }
+    }
+
+    fn percent_of_limit(&self, value_in_kibibyte: u64) -> String {
+        match self.limit_in_kibibyte {
+            Some(limit) if limit > 0 => format!("{:.1}", 100.0 * value_in_kibibyte as f64 / limit as f64),
+            _ => String::new(),
+        }
+    }
+
+    /// Memory left until the limit is reached, based on the cgroup usage if known
+    pub fn headroom_in_kibibyte(&self) -> Option<i64> {
+        let limit = self.limit_in_kibibyte?;
+        let used = self.cgroup
+            .as_ref()
+            .and_then(|cgroup| cgroup.current_in_kibibyte)
+            .unwrap_or(0)
+            .max(self.process.total_in_kibibyte());
+        Some(limit as i64 - used as i64)
+    }
+}
+
+const MEMORY_LIMIT_TABLE_SEPARATOR: &str = "|---------------|----------------|------------|--------------|------------------------|\n";
+
+fn fmt_counter(value: Option<u64>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+impl Display for MemoryLimitAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.limit_in_kibibyte {
+            Some(limit) => writeln!(f, "Limit: {} KiB (from {})", limit, self.limit_source)?,
+            None if self.cgroup.is_some() => writeln!(f, "Limit: none (from {})", self.limit_source)?,
+            None => return writeln!(f, "No memory limit given (--memory-limit or --cgroup)"),
+        }
+        let cgroup = self.cgroup.clone().unwrap_or_default();
+        let rows = [
+            ("Anonymous", Some(self.process.anonymous_in_kibibyte), cgroup.anonymous_in_kibibyte),
+            // the page cache of the cgroup includes its shared memory
+            ("File", Some(self.process.file_in_kibibyte), cgroup.file_in_kibibyte.map(|file| file.saturating_sub(cgroup.shared_memory_in_kibibyte.unwrap_or(0)))),
+            ("Shared Memory", Some(self.process.shared_memory_in_kibibyte), cgroup.shared_memory_in_kibibyte),
+            ("Kernel", None, cgroup.kernel_in_kibibyte),
+            ("Total", Some(self.process.total_in_kibibyte()), cgroup.current_in_kibibyte),
+        ];
+        MEMORY_LIMIT_TABLE_SEPARATOR.fmt(f)?;
+        format!("| {:13} | {:14} | {:10} | {:12} | {:22} |\n", "Kind", "Process [KiB]", "Limit [%]", "cgroup [KiB]", "Outside Process [KiB]").fmt(f)?;
+        MEMORY_LIMIT_TABLE_SEPARATOR.fmt(f)?;
+        for (index, (kind, process, cgroup)) in rows.iter().enumerate() {
+            if index + 1 == rows.len() {
+                MEMORY_LIMIT_TABLE_SEPARATOR.fmt(f)?;
+            }
+            let used = process.or(*cgroup);
+            let outside = cgroup.map(|cgroup| cgroup.saturating_sub(process.unwrap_or(0)));
+            format!(
+                "| {:13} | {:>14} | {:>10} | {:>12} | {:>22} |\n",
+                kind,
+                fmt_counter(*process),
+                used.map(|used| self.percent_of_limit(used)).unwrap_or_default(),
+                fmt_counter(*cgroup),
+                fmt_counter(outside)).fmt(f)?;
+        }
+        MEMORY_LIMIT_TABLE_SEPARATOR.fmt(f)?;
+        if let Some(headroom) = self.headroom_in_kibibyte() {
+            writeln!(f, "Headroom: {} KiB", headroom)?;
+        }
+        if let Some(cgroup) = &self.cgroup {
+            let file_outside = cgroup.file_in_kibibyte
+                .map(|file| file.saturating_sub(cgroup.shared_memory_in_kibibyte.unwrap_or(0)).saturating_sub(self.process.file_in_kibibyte))
+                .unwrap_or(0);
+            if file_outside > 0 {
+                writeln!(f, "{} KiB page cache are charged to the cgroup but not mapped by the process (files read or written, logs, other processes), the kernel reclaims them before the limit is hit", file_outside)?;
+            }
+            if let Some(kernel) = cgroup.kernel_in_kibibyte.filter(|kernel| *kernel > 0) {
+                writeln!(f, "{} KiB kernel memory (slab, kernel stacks, page tables, socket buffers) are charged to the cgroup and invisible in the mappings", kernel)?;
+            }
+            let anonymous_outside = cgroup.anonymous_in_kibibyte.unwrap_or(0).saturating_sub(self.process.anonymous_in_kibibyte);
+            if anonymous_outside > 0 {
+                writeln!(f, "{} KiB anonymous memory belong to other processes of the cgroup", anonymous_outside)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod cgroup_tests {
+    use super::*;
+    use crate::file_info::FileInfo;
+
+    #[test]
+    fn reconcile_process_with_cgroup_counters() {
+        let cgroup = CgroupMemory::parse_v2(
+            "536870912\n",
+            Some("209715200\n"),
+            Some("anon 104857600\nfile 83886080\nkernel 10485760\nshmem 4194304\nfile_mapped 20971520\n"));
+        assert_eq!(
+            (cgroup.limit_in_kibibyte, cgroup.current_in_kibibyte, cgroup.anonymous_in_kibibyte, cgroup.file_in_kibibyte, cgroup.shared_memory_in_kibibyte, cgroup.kernel_in_kibibyte),
+            (Some(512 * 1024), Some(200 * 1024), Some(100 * 1024), Some(80 * 1024), Some(4 * 1024), Some(10 * 1024)));
+        assert_eq!(CgroupMemory::parse_v2("max\n", None, None).limit_in_kibibyte, None);
+        let cgroup_v1 = CgroupMemory::parse_v1("9223372036854771712\n", Some("1048576\n"), Some("cache 0\nrss 1048576\ntotal_rss 2097152\n"), None);
+        assert_eq!((cgroup_v1.limit_in_kibibyte, cgroup_v1.anonymous_in_kibibyte), (None, Some(2048)));
+
+        let page = |mapping_kind: MappingKind, resident_set_size_in_kibibyte: u64, anonymous_in_kibibyte: u64| PMap {
+            size_in_kibibyte: resident_set_size_in_kibibyte,
+            resident_set_size_in_kibibyte,
+            anonymous_in_kibibyte,
+            mapping_kind,
+            ..Default::default()
+        };
+        let pages = PMapVec(vec![
+            page(MappingKind::AnonymousPrivate(None), 90 * 1024, 90 * 1024),
+            page(MappingKind::File(FileInfo::new("/usr/lib/libcoreclr.so")), 6 * 1024, 1024),
+            page(MappingKind::File(FileInfo::new("/memfd:doublemapper (deleted)")), 2048, 0),
+        ]);
+        let analysis = MemoryLimitAnalysis::analyze(&pages, None, Some(cgroup));
+        assert_eq!(analysis.process, ProcessMemory { anonymous_in_kibibyte: 91 * 1024, shared_memory_in_kibibyte: 2048, file_in_kibibyte: 5 * 1024 });
+        assert_eq!(analysis.headroom_in_kibibyte(), Some(312 * 1024));
+        assert!(analysis.to_string().contains("72704 KiB page cache are charged to the cgroup but not mapped"));
+    }
+}
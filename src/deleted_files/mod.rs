@@ -0,0 +1,142 @@
+use std::fmt::Display;
+
+use crate::pmap::*;
+
+// the kernel appends this to the path of a mapped file that was removed or replaced on disk
+const DELETED_SUFFIX: &str = " (deleted)";
+
+/// What a mapping of a deleted file most likely is
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DeletedFileKind {
+    // executable mapping of a library that was replaced on disk (e.g. by an in-place upgrade), the process still runs the old code
+    StaleCode,
+    // data file or non-executable part of a library that was replaced on disk
+    StaleData,
+    // memfd, which is still mapped although nothing refers to it anymore
+    LeakedMemory,
+}
+
+impl Display for DeletedFileKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeletedFileKind::StaleCode => "Stale Code".fmt(f),
+            DeletedFileKind::StaleData => "Stale Data".fmt(f),
+            DeletedFileKind::LeakedMemory => "Leaked Memory".fmt(f),
+        }
+    }
+}
+
+/// Deleted file with all of its mappings
+#[derive(Debug, PartialEq)]
+pub struct DeletedFile<'a> {
+    // path without the ` (deleted)` suffix
+    pub path: String,
+    pub kind: DeletedFileKind,
+    pub pages: Vec<&'a PMap>,
+}
+
+impl DeletedFile<'_> {
+    pub fn size_in_kibibyte(&self) -> u64 {
+        self.pages.iter().map(|page| page.size_in_kibibyte).sum()
+    }
+
+    pub fn resident_set_size_in_kibibyte(&self) -> u64 {
+        self.pages.iter().map(|page| page.resident_set_size_in_kibibyte).sum()
+    }
+
+    pub fn proportional_share_size_in_kibibyte(&self) -> u64 {
+        self.pages.iter().map(|page| page.proportional_share_size_in_kibibyte).sum()
+    }
+
+    pub fn is_memfd(&self) -> bool {
+        self.path.trim_start_matches('/').starts_with("memfd:")
+    }
+}
+
+pub struct DeletedFileVec<'a>(pub Vec<DeletedFile<'a>>);
+
+impl<'a> DeletedFileVec<'a> {
+    /// Groups the mappings of deleted files (except the JIT doublemapper) by their original path, ordered by RSS
+    pub fn find(memory_pages: &'a PMapVec) -> Self {
+        let mut files: Vec<DeletedFile> = Vec::new();
+        for page in &memory_pages.0 {
+            let MappingKind::File(file_info) = &page.mapping_kind else {
+                continue;
+            };
+            if page.mapping_kind.is_doublemapper() {
+                continue;
+            }
+            let full_name = file_info.full_name();
+            let Some(path) = full_name.strip_suffix(DELETED_SUFFIX) else {
+                continue;
+            };
+            match files.iter_mut().find(|file| file.path == path) {
+                Some(file) => file.pages.push(page),
+                None => files.push(DeletedFile { path: path.to_string(), kind: DeletedFileKind::StaleData, pages: vec![page] }),
+            }
+        }
+        for file in files.iter_mut() {
+            file.kind = if file.is_memfd() {
+                DeletedFileKind::LeakedMemory
+            } else if file.pages.iter().any(|page| page.permissions.contains(Permissions::Execute)) {
+                DeletedFileKind::StaleCode
+            } else {
+                DeletedFileKind::StaleData
+            };
+        }
+        files.sort_by_key(|file| std::cmp::Reverse(file.resident_set_size_in_kibibyte()));
+        DeletedFileVec(files)
+    }
+}
+
+const DELETED_FILE_TABLE_SEPARATOR: &str = "|----------------------------------------------------------|---------------|------------|------------|------------|------------|\n";
+
+impl Display for DeletedFileVec<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return writeln!(f, "No deleted files are mapped (besides the JIT doublemapper)");
+        }
+        DELETED_FILE_TABLE_SEPARATOR.fmt(f)?;
+        format!("| {:56} | {:13} | {:10} | {:10} | {:10} | {:10} |\n", "Deleted File", "Kind", "#Mappings", "Size [KiB]", "RSS [KiB]", "PSS [KiB]").fmt(f)?;
+        DELETED_FILE_TABLE_SEPARATOR.fmt(f)?;
+        for file in &self.0 {
+            format!(
+                "| {:56} | {:13} | {:10} | {:10} | {:10} | {:10} |\n",
+                file.path,
+                file.kind.to_string(),
+                file.pages.len(),
+                file.size_in_kibibyte(),
+                file.resident_set_size_in_kibibyte(),
+                file.proportional_share_size_in_kibibyte()).fmt(f)?;
+        }
+        DELETED_FILE_TABLE_SEPARATOR.fmt(f)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod deleted_files_tests {
+    use super::*;
+    use crate::file_info::FileInfo;
+
+    #[test]
+    fn group_and_flag_deleted_files() {
+        let pages = PMapVec(vec![
+            PMap { resident_set_size_in_kibibyte: 100, ..PMap::test_page(0, "r--p", 1024, MappingKind::File(FileInfo::new("/usr/lib/libssl.so.3 (deleted)"))) },
+            PMap { resident_set_size_in_kibibyte: 300, ..PMap::test_page(0, "r-xp", 1024, MappingKind::File(FileInfo::new("/usr/lib/libssl.so.3 (deleted)"))) },
+            PMap { resident_set_size_in_kibibyte: 1024, ..PMap::test_page(0, "rw-s", 1024, MappingKind::File(FileInfo::new("/memfd:buffer (deleted)"))) },
+            PMap { resident_set_size_in_kibibyte: 50, ..PMap::test_page(0, "r--s", 1024, MappingKind::File(FileInfo::new("/var/cache/app.db (deleted)"))) },
+            PMap { resident_set_size_in_kibibyte: 1024, ..PMap::test_page(0, "r-xs", 1024, MappingKind::File(FileInfo::new("/memfd:doublemapper (deleted)"))) },
+            PMap { resident_set_size_in_kibibyte: 1024, ..PMap::test_page(0, "r-xp", 1024, MappingKind::File(FileInfo::new("/usr/lib/libc.so.6"))) },
+        ]);
+
+        let deleted = DeletedFileVec::find(&pages);
+        let found = deleted.0.iter().map(|file| (file.path.as_str(), file.kind, file.pages.len())).collect::<Vec<_>>();
+        assert_eq!(found, vec![
+            ("/memfd:buffer", DeletedFileKind::LeakedMemory, 1),
+            ("/usr/lib/libssl.so.3", DeletedFileKind::StaleCode, 2),
+            ("/var/cache/app.db", DeletedFileKind::StaleData, 1),
+        ]);
+        assert_eq!(deleted.0[1].resident_set_size_in_kibibyte(), 400);
+    }
+}
@@ -0,0 +1,440 @@
+use enumflags2::BitFlags;
+use std::fmt::Display;
+
+use crate::pmap::*;
+
+// The .NET runtime reserves its memory with mmap(PROT_NONE) + madvise(MADV_DONTDUMP) and commits
+// parts of the reservation later with mprotect(PROT_READ | PROT_WRITE) + madvise(MADV_DODUMP), so
+// reserved but uncommitted .NET memory shows up as `---p` anonymous pages with the `dd` VmFlag.
+// glibc reserves its per thread malloc arenas with MAP_NORESERVE (`nr` VmFlag) aligned to 64 MiB
+// and pthread puts a PROT_NONE guard page below every thread stack.
+
+// reservations bigger than 4 GiB are only done by the GC (regions range)
+const MIN_GC_RESERVATION_IN_KIBIBYTE: u64 = 4 * 1024 * 1024;
+// glibc HEAP_MAX_SIZE on 64 bit systems
+const MALLOC_ARENA_SIZE_IN_KIBIBYTE: u64 = 64 * 1024;
+// guard pages are one page, which is at most 64 KiB (arm64 with 64 KiB pages)
+const MAX_GUARD_PAGE_SIZE_IN_KIBIBYTE: u64 = 64;
+// runtime reservations bigger than this are unlikely to be loader heaps
+const MAX_LOADER_HEAP_RESERVATION_IN_KIBIBYTE: u64 = 64 * 1024;
+
+/// Kind of memory region the .NET runtime (or the native code it uses) creates with anonymous mappings
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DotnetRegionKind {
+    /// Regions (or segments) of the managed heap
+    GcHeap,
+    /// Card table, brick table, mark array, ... of the GC
+    GcBookkeeping,
+    /// Reservations of the loader heaps (type system, stubs data, ...)
+    LoaderHeap,
+    /// Executable memory of the JIT and stubs (including reserved address space between the W^X views)
+    ExecutableAllocator,
+    /// Stack of a thread
+    ThreadStack,
+    /// PROT_NONE page protecting against stack (or buffer) overflows
+    GuardPage,
+    /// Per thread arena of the native allocator
+    MallocArena,
+}
+
+impl Display for DotnetRegionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DotnetRegionKind::GcHeap => ".NET GC Heap".fmt(f),
+            DotnetRegionKind::GcBookkeeping => ".NET GC Bookkeeping".fmt(f),
+            DotnetRegionKind::LoaderHeap => ".NET Loader Heaps".fmt(f),
+            DotnetRegionKind::ExecutableAllocator => ".NET Executable Allocator".fmt(f),
+            DotnetRegionKind::ThreadStack => "Thread Stacks".fmt(f),
+            DotnetRegionKind::GuardPage => "Guard Pages".fmt(f),
+            DotnetRegionKind::MallocArena => "Native Malloc Arenas".fmt(f),
+        }
+    }
+}
+
+/// How sure the classifier is about the kind of a region
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl Display for Confidence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Confidence::Low => "Low".fmt(f),
+            Confidence::Medium => "Medium".fmt(f),
+            Confidence::High => "High".fmt(f),
+        }
+    }
+}
+
+/// Anonymous memory page together with the kind of region it belongs to
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClassifiedRegion {
+    pub address: u64,
+    pub size_in_kibibyte: u64,
+    pub resident_set_size_in_kibibyte: u64,
+    pub kind: DotnetRegionKind,
+    pub confidence: Confidence,
+}
+
+/// Address range [start, end) reserved by the GC for its regions
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GcReservation {
+    pub start: u64,
+    pub end: u64,
+    pub confidence: Confidence,
+}
+
+impl GcReservation {
+    pub fn size_in_kibibyte(&self) -> u64 {
+        (self.end - self.start) / 1024
+    }
+
+    pub fn contains(&self, page: &PMap) -> bool {
+        page.address >= self.start && page.end_address() <= self.end
+    }
+
+    /// Finds the biggest reservation done by the GC, the reserved range is a power of two (256 GiB by default),
+    /// which allows to separate it from the GC bookkeeping that is reserved directly in front of it
+    pub fn find(memory_pages: &PMapVec) -> Option<GcReservation> {
+        let pages = sorted_by_address(memory_pages);
+        let huge = pages
+            .iter()
+            .enumerate()
+            .filter(|(_, page)| is_reserved(page) && page.virtual_memory_flags.contains(VirtualMemoryFlags::DoNotIncludeInCoreDump))
+            .filter(|(_, page)| page.size_in_kibibyte >= MIN_GC_RESERVATION_IN_KIBIBYTE)
+            .max_by_key(|(_, page)| page.size_in_kibibyte)
+            .map(|(index, _)| index)?;
+
+        let (first, last) = contiguous_anonymous_run(&pages, huge);
+        let run_size = pages[last].end_address() - pages[first].address;
+        let mut size = pages[huge].size_in_kibibyte.next_power_of_two() * 1024;
+        while size <= run_size {
+            for candidate in &pages[first..=huge] {
+                let end = candidate.address + size;
+                if end >= pages[huge].end_address() && pages[huge..=last].iter().any(|page| page.end_address() == end) {
+                    return Some(GcReservation { start: candidate.address, end, confidence: Confidence::High });
+                }
+            }
+            size *= 2;
+        }
+
+        // reservation with an unusual size, take the huge reservation and the regions committed in front of it
+        let mut start = huge;
+        while start > first && pages[start - 1].permissions == make_permissions("rw-p") {
+            start -= 1;
+        }
+        Some(GcReservation { start: pages[start].address, end: pages[huge].end_address(), confidence: Confidence::Medium })
+    }
+}
+
+pub struct ClassifiedRegionVec(pub Vec<ClassifiedRegion>);
+
+impl ClassifiedRegionVec {
+    /// Uses size, alignment, permission and adjacency patterns of the CoreCLR runtime and glibc to find out
+    /// what the unnamed anonymous memory pages are used for, pages without known pattern are not part of the result
+    /// (named pages only if glibc named them as malloc arena)
+    pub fn classify(memory_pages: &PMapVec, thread_stack_size_in_kibibyte: u64) -> Self {
+        let pages = sorted_by_address(memory_pages);
+        let mut labels: Vec<Option<(DotnetRegionKind, Confidence)>> = vec![None; pages.len()];
+
+        if let Some(reservation) = GcReservation::find(memory_pages) {
+            for (index, page) in pages.iter().enumerate() {
+                if is_anonymous(page) && reservation.contains(page) {
+                    labels[index] = Some((DotnetRegionKind::GcHeap, reservation.confidence));
+                }
+            }
+
+            // card table, mark array & co are reserved directly below the regions range, the last of them ends where the range starts
+            if let Some(mut index) = pages.iter().position(|page| page.end_address() == reservation.start) {
+                let max_size = reservation.size_in_kibibyte() / 16;
+                let mut size = 0;
+                loop {
+                    let page = pages[index];
+                    size += page.size_in_kibibyte;
+                    if !is_anonymous(page) || labels[index].is_some() || size > max_size {
+                        break;
+                    }
+                    labels[index] = Some((DotnetRegionKind::GcBookkeeping, Confidence::Medium));
+                    if index == 0 || pages[index - 1].end_address() != page.address {
+                        break;
+                    }
+                    index -= 1;
+                }
+            }
+        }
+
+        for (index, page) in pages.iter().enumerate() {
+            if !is_anonymous(page) || labels[index].is_some() {
+                continue;
+            }
+            let previous = index.checked_sub(1).map(|i| pages[i]).filter(|previous| previous.end_address() == page.address);
+            let next = pages.get(index + 1).copied().filter(|next| page.end_address() == next.address);
+            let next_to = |matches: fn(&PMap) -> bool| previous.is_some_and(matches) || next.is_some_and(matches);
+            if page.permissions.contains(Permissions::Execute) {
+                // without W^X the JIT writes its code into read/write/execute memory, read/execute memory is only
+                // known to be the runtime's next to its doublemapper views or inside of one of its reservations
+                let confidence = if page.permissions.contains(Permissions::Write) || next_to(|p| p.mapping_kind.is_doublemapper() || is_runtime_reservation(p)) {
+                    Confidence::High
+                } else {
+                    Confidence::Low
+                };
+                labels[index] = Some((DotnetRegionKind::ExecutableAllocator, confidence));
+            } else if is_runtime_reservation(page) && next_to(|p| p.mapping_kind.is_doublemapper()) {
+                labels[index] = Some((DotnetRegionKind::ExecutableAllocator, Confidence::High));
+            }
+        }
+
+        // with the tunable glibc.mem.decorate_maps glibc names the arenas itself
+        for (index, page) in pages.iter().enumerate() {
+            if is_named_malloc_arena(page) {
+                labels[index] = Some((DotnetRegionKind::MallocArena, Confidence::High));
+            }
+        }
+
+        for index in 0..pages.len() {
+            let head = pages[index];
+            if !is_anonymous(head) || labels[index].is_some() || !head.address.is_multiple_of(MALLOC_ARENA_SIZE_IN_KIBIBYTE * 1024) {
+                continue;
+            }
+            if head.permissions != make_permissions("rw-p") {
+                continue;
+            }
+            let tail = pages.get(index + 1).filter(|tail| is_anonymous(tail) && is_reserved(tail) && head.end_address() == tail.address);
+            let size = head.size_in_kibibyte + tail.map(|tail| tail.size_in_kibibyte).unwrap_or(0);
+            let no_reserve = head.virtual_memory_flags.contains(VirtualMemoryFlags::SwapSpaceIsNotReservedForTheArea);
+            let confidence = match (no_reserve, size == MALLOC_ARENA_SIZE_IN_KIBIBYTE) {
+                (true, _) if size <= MALLOC_ARENA_SIZE_IN_KIBIBYTE => Confidence::High,
+                (false, true) => Confidence::Medium,
+                _ => continue,
+            };
+            labels[index] = Some((DotnetRegionKind::MallocArena, confidence));
+            if tail.is_some() {
+                labels[index + 1] = Some((DotnetRegionKind::MallocArena, confidence));
+            }
+        }
+
+        for index in 0..pages.len() {
+            let guard = pages[index];
+            if !is_anonymous(guard) || labels[index].is_some() || !is_reserved(guard) || guard.size_in_kibibyte > MAX_GUARD_PAGE_SIZE_IN_KIBIBYTE {
+                continue;
+            }
+            if guard.virtual_memory_flags.intersects(VirtualMemoryFlags::DoNotIncludeInCoreDump | VirtualMemoryFlags::SwapSpaceIsNotReservedForTheArea) {
+                continue;
+            }
+            let stack = pages.get(index + 1)
+                .filter(|stack| is_anonymous(stack) && labels[index + 1].is_none() && guard.end_address() == stack.address)
+                .filter(|stack| stack.permissions == make_permissions("rw-p"));
+            let Some(stack) = stack else {
+                continue;
+            };
+            if stack.size_in_kibibyte == thread_stack_size_in_kibibyte || stack.size_in_kibibyte + guard.size_in_kibibyte == thread_stack_size_in_kibibyte {
+                labels[index] = Some((DotnetRegionKind::GuardPage, Confidence::High));
+                labels[index + 1] = Some((DotnetRegionKind::ThreadStack, Confidence::High));
+            } else if stack.size_in_kibibyte >= 64 {
+                labels[index] = Some((DotnetRegionKind::GuardPage, Confidence::High));
+                labels[index + 1] = Some((DotnetRegionKind::ThreadStack, Confidence::Medium));
+            } else {
+                labels[index] = Some((DotnetRegionKind::GuardPage, Confidence::Medium));
+            }
+        }
+
+        // the remaining reservations of the runtime are mostly loader heaps, which commit from the start of the reservation
+        for index in 0..pages.len() {
+            let page = pages[index];
+            if !is_anonymous(page) || labels[index].is_some() || !is_runtime_reservation(page) {
+                continue;
+            }
+            let confidence = if page.size_in_kibibyte <= MAX_LOADER_HEAP_RESERVATION_IN_KIBIBYTE { Confidence::Medium } else { Confidence::Low };
+            labels[index] = Some((DotnetRegionKind::LoaderHeap, confidence));
+            let mut committed = index;
+            while committed > 0
+                && labels[committed - 1].is_none()
+                && is_anonymous(pages[committed - 1])
+                && pages[committed - 1].end_address() == pages[committed].address
+                && pages[committed - 1].permissions == make_permissions("rw-p") {
+                committed -= 1;
+                labels[committed] = Some((DotnetRegionKind::LoaderHeap, confidence));
+            }
+        }
+
+        let regions = pages
+            .iter()
+            .zip(labels)
+            .filter_map(|(page, label)| label.map(|(kind, confidence)| ClassifiedRegion {
+                address: page.address,
+                size_in_kibibyte: page.size_in_kibibyte,
+                resident_set_size_in_kibibyte: page.resident_set_size_in_kibibyte,
+                kind,
+                confidence,
+            }))
+            .collect();
+        ClassifiedRegionVec(regions)
+    }
+
+    /// Returns the classification of the memory page starting at `address`
+    pub fn find(&self, address: u64) -> Option<&ClassifiedRegion> {
+        self.0
+            .binary_search_by_key(&address, |region| region.address)
+            .ok()
+            .map(|index| &self.0[index])
+    }
+}
+
+fn sorted_by_address(memory_pages: &PMapVec) -> Vec<&PMap> {
+    let mut pages = memory_pages.0.iter().collect::<Vec<_>>();
+    pages.sort_by_key(|page| page.address);
+    pages
+}
+
+fn is_anonymous(page: &PMap) -> bool {
+    page.mapping_kind == MappingKind::AnonymousPrivate(None)
+}
+
+/// Returns true for `[anon: glibc: malloc arena]` pages (glibc 2.39+ with `GLIBC_TUNABLES=glibc.mem.decorate_maps=1`)
+pub fn is_named_malloc_arena(page: &PMap) -> bool {
+    matches!(&page.mapping_kind, MappingKind::AnonymousPrivate(Some(name)) if name.trim() == "glibc: malloc arena")
+}
+
+// reserved address space without any access rights
+fn is_reserved(page: &PMap) -> bool {
+    page.permissions == make_permissions("---p")
+}
+
+// address space reserved by the runtime (mmap(PROT_NONE) + madvise(MADV_DONTDUMP))
+fn is_runtime_reservation(page: &PMap) -> bool {
+    is_anonymous(page) && is_reserved(page) && page.virtual_memory_flags.contains(VirtualMemoryFlags::DoNotIncludeInCoreDump)
+}
+
+fn make_permissions(s: &str) -> BitFlags<Permissions> {
+    <BitFlags<Permissions> as MyFromStr>::from_str(s).unwrap_or_default()
+}
+
+// first and last index of the anonymous pages without gaps around `index`
+fn contiguous_anonymous_run(pages: &[&PMap], index: usize) -> (usize, usize) {
+    let mut first = index;
+    while first > 0 && is_anonymous(pages[first - 1]) && pages[first - 1].end_address() == pages[first].address {
+        first -= 1;
+    }
+    let mut last = index;
+    while last + 1 < pages.len() && is_anonymous(pages[last + 1]) && pages[last].end_address() == pages[last + 1].address {
+        last += 1;
+    }
+    (first, last)
+}
+
+const CLASSIFICATION_TABLE_SEPARATOR: &str = "|--------------------------------|------------|------------|------------|-----------------|\n";
+
+impl Display for ClassifiedRegionVec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut summary: Vec<(DotnetRegionKind, Confidence, u64, u64, usize)> = Vec::new();
+        for region in &self.0 {
+            match summary.iter_mut().find(|(kind, confidence, ..)| *kind == region.kind && *confidence == region.confidence) {
+                Some(entry) => {
+                    entry.2 += region.size_in_kibibyte;
+                    entry.3 += region.resident_set_size_in_kibibyte;
+                    entry.4 += 1;
+                }
+                None => summary.push((region.kind, region.confidence, region.size_in_kibibyte, region.resident_set_size_in_kibibyte, 1)),
+            }
+        }
+        summary.sort_by_key(|(kind, confidence, ..)| (*kind, std::cmp::Reverse(*confidence)));
+
+        CLASSIFICATION_TABLE_SEPARATOR.fmt(f)?;
+        format!("| {:30} | {:10} | {:10} | {:10} | {:15} |\n", "Region Kind", "Confidence", "Size [KiB]", "RSS [KiB]", "#Memory Pages").fmt(f)?;
+        CLASSIFICATION_TABLE_SEPARATOR.fmt(f)?;
+        for (kind, confidence, size, rss, count) in summary {
+            format!("| {:30} | {:10} | {:10} | {:10} | {:15} |\n", kind.to_string(), confidence.to_string(), size, rss, count).fmt(f)?;
+        }
+        CLASSIFICATION_TABLE_SEPARATOR.fmt(f)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod dotnet_regions_tests {
+    use super::*;
+
+    fn flags(flags: &str) -> BitFlags<VirtualMemoryFlags> {
+        <BitFlags<VirtualMemoryFlags> as MyFromStr>::from_str(flags).unwrap()
+    }
+
+    fn kind_of(regions: &ClassifiedRegionVec, address: u64) -> Option<(DotnetRegionKind, Confidence)> {
+        regions.find(address).map(|region| (region.kind, region.confidence))
+    }
+
+    #[test]
+    fn gc_reservation_and_bookkeeping() {
+        let gib = 1024 * 1024 * 1024;
+        let start = 0x7f0000000000;
+        let pages = PMapVec(vec![
+            // bookkeeping in front of the regions range
+            PMap { virtual_memory_flags: flags("rd wr mr mw me ac sd"), ..PMap::test_page(start - 2 * gib, "rw-p", 64, MappingKind::AnonymousPrivate(None)) },
+            PMap { virtual_memory_flags: flags("mr mw me dd sd"), ..PMap::test_page(start - 2 * gib + 64 * 1024, "---p", 2 * 1024 * 1024 - 64, MappingKind::AnonymousPrivate(None)) },
+            // 256 GiB regions range
+            PMap { virtual_memory_flags: flags("rd wr mr mw me ac sd"), ..PMap::test_page(start, "rw-p", 4096, MappingKind::AnonymousPrivate(None)) },
+            PMap { virtual_memory_flags: flags("mr mw me dd sd"), ..PMap::test_page(start + 4 * 1024 * 1024, "---p", 256 * 1024 * 1024 - 4096, MappingKind::AnonymousPrivate(None)) },
+        ]);
+
+        let reservation = GcReservation::find(&pages).unwrap();
+        assert_eq!(reservation.start, start);
+        assert_eq!(reservation.end, start + 256 * gib);
+        assert_eq!(reservation.confidence, Confidence::High);
+
+        let regions = ClassifiedRegionVec::classify(&pages, 8192);
+        assert_eq!(kind_of(&regions, start), Some((DotnetRegionKind::GcHeap, Confidence::High)));
+        assert_eq!(kind_of(&regions, start - 2 * gib), Some((DotnetRegionKind::GcBookkeeping, Confidence::Medium)));
+    }
+
+    #[test]
+    fn malloc_arena_thread_stack_and_guard_page() {
+        let pages = PMapVec(vec![
+            PMap { virtual_memory_flags: flags("rd wr mr mw me nr sd"), ..PMap::test_page(0x7f6e44000000, "rw-p", 132, MappingKind::AnonymousPrivate(None)) },
+            PMap { virtual_memory_flags: flags("mr mw me nr sd"), ..PMap::test_page(0x7f6e44021000, "---p", 65404, MappingKind::AnonymousPrivate(None)) },
+            PMap { virtual_memory_flags: flags("mr mw me sd"), ..PMap::test_page(0x7f6e52ffe000, "---p", 4, MappingKind::AnonymousPrivate(None)) },
+            PMap { virtual_memory_flags: flags("rd wr mr mw me ac sd"), ..PMap::test_page(0x7f6e52fff000, "rw-p", 8192, MappingKind::AnonymousPrivate(None)) },
+            PMap { virtual_memory_flags: flags("rd wr mr mw me ac sd"), ..PMap::test_page(0x7f6e60000000, "rw-p", 100, MappingKind::AnonymousPrivate(None)) },
+        ]);
+
+        let regions = ClassifiedRegionVec::classify(&pages, 8192);
+        assert_eq!(kind_of(&regions, 0x7f6e44000000), Some((DotnetRegionKind::MallocArena, Confidence::High)));
+        assert_eq!(kind_of(&regions, 0x7f6e44021000), Some((DotnetRegionKind::MallocArena, Confidence::High)));
+        assert_eq!(kind_of(&regions, 0x7f6e52ffe000), Some((DotnetRegionKind::GuardPage, Confidence::High)));
+        assert_eq!(kind_of(&regions, 0x7f6e52fff000), Some((DotnetRegionKind::ThreadStack, Confidence::High)));
+        assert_eq!(kind_of(&regions, 0x7f6e60000000), None);
+
+        let named = PMap::test_page(0x7f6e48000000, "rw-p", 132, MappingKind::AnonymousPrivate(Some(" glibc: malloc arena".to_string())));
+        let regions = ClassifiedRegionVec::classify(&PMapVec(vec![named]), 8192);
+        assert_eq!(kind_of(&regions, 0x7f6e48000000), Some((DotnetRegionKind::MallocArena, Confidence::High)));
+    }
+
+    #[test]
+    fn executable_and_loader_heap_reservations() {
+        let doublemapper = PMap {
+            address: 0x7faf62000000,
+            size_in_kibibyte: 64,
+            mapping_kind: MappingKind::File(crate::file_info::FileInfo::new("/memfd:doublemapper (deleted)")),
+            ..Default::default()
+        };
+        let pages = PMapVec(vec![
+            doublemapper,
+            PMap { virtual_memory_flags: flags("mr mw me dd sd"), ..PMap::test_page(0x7faf62010000, "---p", 64, MappingKind::AnonymousPrivate(None)) },
+            PMap { virtual_memory_flags: flags("rd wr mr mw me ac sd"), ..PMap::test_page(0x7faf63000000, "rw-p", 8, MappingKind::AnonymousPrivate(None)) },
+            PMap { virtual_memory_flags: flags("mr mw me dd sd"), ..PMap::test_page(0x7faf63002000, "---p", 120, MappingKind::AnonymousPrivate(None)) },
+            PMap { virtual_memory_flags: flags("rd wr ex mr mw me ac sd"), ..PMap::test_page(0x7faf64000000, "rwxp", 4, MappingKind::AnonymousPrivate(None)) },
+        ]);
+
+        let regions = ClassifiedRegionVec::classify(&pages, 8192);
+        assert_eq!(kind_of(&regions, 0x7faf62010000), Some((DotnetRegionKind::ExecutableAllocator, Confidence::High)));
+        assert_eq!(kind_of(&regions, 0x7faf63000000), Some((DotnetRegionKind::LoaderHeap, Confidence::Medium)));
+        assert_eq!(kind_of(&regions, 0x7faf63002000), Some((DotnetRegionKind::LoaderHeap, Confidence::Medium)));
+        assert_eq!(kind_of(&regions, 0x7faf64000000), Some((DotnetRegionKind::ExecutableAllocator, Confidence::High)));
+
+        // read/execute memory without doublemapper view or reservation next to it is only a guess
+        let lone = PMapVec(vec![PMap::test_page(0x7faf65000000, "r-xp", 4, MappingKind::AnonymousPrivate(None))]);
+        let regions = ClassifiedRegionVec::classify(&lone, 8192);
+        assert_eq!(kind_of(&regions, 0x7faf65000000), Some((DotnetRegionKind::ExecutableAllocator, Confidence::Low)));
+    }
+}
@@ -0,0 +1,157 @@
+use std::fmt::Display;
+
+use crate::images::{LoadedImage, LoadedImageVec, SegmentKind};
+
+// satellite assemblies have the same file name for every culture, but a different content
+const SATELLITE_ASSEMBLY_SUFFIX: &str = ".resources.dll";
+
+// .NET maps an assembly as flat file (to read the metadata) and with its sections laid out, so one flat view
+// next to the loaded image is expected
+fn is_flat_view(image: &LoadedImage) -> bool {
+    image.segments.iter().all(|segment| matches!(segment.kind, SegmentKind::ReadOnlyData | SegmentKind::Headers))
+}
+
+/// Why the images are considered to be duplicates
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DuplicateKind {
+    // the same file (device and inode) is mapped more than once, e.g. an assembly loaded into several
+    // AssemblyLoadContexts or a library loaded under two paths via symlinks
+    SameFile,
+    // files with the same name but a different inode, e.g. two copies of System.Private.CoreLib.dll
+    SameName,
+}
+
+impl Display for DuplicateKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DuplicateKind::SameFile => "Same File".fmt(f),
+            DuplicateKind::SameName => "Same Name".fmt(f),
+        }
+    }
+}
+
+/// Image loaded more than once
+#[derive(Debug, PartialEq)]
+pub struct DuplicateImage<'a> {
+    pub kind: DuplicateKind,
+    pub name: String,
+    // ordered by address
+    pub copies: Vec<&'a LoadedImage>,
+}
+
+impl DuplicateImage<'_> {
+    /// Memory, that would be saved if the image was loaded only once. Mappings of the same file share the
+    /// page cache, so only the private dirty memory (relocations, .bss) of the further copies is wasted.
+    /// Different files don't share anything, so the RSS of all files but the biggest one is wasted.
+    pub fn wasted_in_kibibyte(&self) -> u64 {
+        let values = match self.kind {
+            DuplicateKind::SameFile => self.copies.iter().map(|image| image.private_dirty_in_kibibyte()).collect::<Vec<_>>(),
+            DuplicateKind::SameName => {
+                let mut files: Vec<((u16, u16, u64), u64)> = Vec::new();
+                for image in &self.copies {
+                    let file = (image.device_major, image.device_minor, image.inode);
+                    match files.iter_mut().find(|(other, _)| *other == file) {
+                        Some((_, resident_set_size)) => *resident_set_size += image.resident_set_size_in_kibibyte(),
+                        None => files.push((file, image.resident_set_size_in_kibibyte())),
+                    }
+                }
+                files.into_iter().map(|(_, resident_set_size)| resident_set_size).collect()
+            }
+        };
+        values.iter().sum::<u64>() - values.iter().max().copied().unwrap_or(0)
+    }
+}
+
+pub struct DuplicateImageVec<'a>(pub Vec<DuplicateImage<'a>>);
+
+impl<'a> DuplicateImageVec<'a> {
+    pub fn find(images: &'a LoadedImageVec) -> Self {
+        let mut duplicates: Vec<DuplicateImage> = Vec::new();
+        for image in &images.0 {
+            let same_file = duplicates.iter_mut().find(|duplicate| {
+                duplicate.kind == DuplicateKind::SameFile && duplicate.copies[0].device_major == image.device_major
+                    && duplicate.copies[0].device_minor == image.device_minor && duplicate.copies[0].inode == image.inode
+            });
+            match same_file {
+                Some(duplicate) => duplicate.copies.push(image),
+                None => duplicates.push(DuplicateImage { kind: DuplicateKind::SameFile, name: image.name().to_string(), copies: vec![image] }),
+            }
+            if image.name().ends_with(SATELLITE_ASSEMBLY_SUFFIX) {
+                continue;
+            }
+            match duplicates.iter_mut().find(|duplicate| duplicate.kind == DuplicateKind::SameName && duplicate.name == image.name()) {
+                Some(duplicate) => duplicate.copies.push(image),
+                None => duplicates.push(DuplicateImage { kind: DuplicateKind::SameName, name: image.name().to_string(), copies: vec![image] }),
+            }
+        }
+        duplicates.retain(|duplicate| match duplicate.kind {
+            DuplicateKind::SameFile => {
+                let flat_views = duplicate.copies.iter().filter(|image| is_flat_view(image)).count();
+                flat_views > 1 || duplicate.copies.len() - flat_views > 1
+            }
+            DuplicateKind::SameName => duplicate.copies.iter().any(|image| image.inode != duplicate.copies[0].inode
+                || image.device_major != duplicate.copies[0].device_major
+                || image.device_minor != duplicate.copies[0].device_minor),
+        });
+        duplicates.sort_by_key(|duplicate| std::cmp::Reverse(duplicate.wasted_in_kibibyte()));
+        DuplicateImageVec(duplicates)
+    }
+
+    pub fn wasted_in_kibibyte(&self) -> u64 {
+        self.0.iter().map(|duplicate| duplicate.wasted_in_kibibyte()).sum()
+    }
+}
+
+impl Display for DuplicateImageVec<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return writeln!(f, "No image is loaded more than once");
+        }
+        writeln!(f, "Duplicate Images: {} (Wasted: {} KiB)", self.0.len(), self.wasted_in_kibibyte())?;
+        for duplicate in &self.0 {
+            writeln!(f, "{}: {} ({} copies, Wasted: {} KiB)", duplicate.kind, duplicate.name, duplicate.copies.len(), duplicate.wasted_in_kibibyte())?;
+            for image in &duplicate.copies {
+                writeln!(
+                    f,
+                    "  0x{:x} - 0x{:x} {:>3}:{:<3} {:>10} RSS: {} KiB, Private Dirty: {} KiB, {}",
+                    image.start_address(),
+                    image.end_address(),
+                    image.device_major,
+                    image.device_minor,
+                    image.inode,
+                    image.resident_set_size_in_kibibyte(),
+                    image.private_dirty_in_kibibyte(),
+                    image.path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod duplicate_images_tests {
+    use super::*;
+    use crate::file_info::FileInfo;
+    use crate::pmap::*;
+
+    #[test]
+    fn same_file_and_same_name() {
+        let pages = PMapVec(vec![
+            PMap { device_major: 8, device_minor: 1, inode: 100, resident_set_size_in_kibibyte: 500, private_dirty_in_kibibyte: 20, ..PMap::test_page(0x10000000, "r-xp", 1024, MappingKind::File(FileInfo::new("/app/Plugin.dll"))) },
+            PMap { device_major: 8, device_minor: 1, inode: 100, resident_set_size_in_kibibyte: 400, private_dirty_in_kibibyte: 30, ..PMap::test_page(0x20000000, "r-xp", 1024, MappingKind::File(FileInfo::new("/app/plugins/Plugin.dll"))) },
+            PMap { device_major: 8, device_minor: 1, inode: 200, resident_set_size_in_kibibyte: 9000, ..PMap::test_page(0x30000000, "r-xp", 1024, MappingKind::File(FileInfo::new("/usr/share/dotnet/System.Private.CoreLib.dll"))) },
+            PMap { device_major: 8, device_minor: 1, inode: 300, resident_set_size_in_kibibyte: 1000, ..PMap::test_page(0x40000000, "r-xp", 1024, MappingKind::File(FileInfo::new("/app/System.Private.CoreLib.dll"))) },
+            PMap { device_major: 8, device_minor: 1, inode: 400, resident_set_size_in_kibibyte: 10, ..PMap::test_page(0x50000000, "r-xp", 1024, MappingKind::File(FileInfo::new("/app/de/App.resources.dll"))) },
+            PMap { device_major: 8, device_minor: 1, inode: 500, resident_set_size_in_kibibyte: 10, ..PMap::test_page(0x60000000, "r-xp", 1024, MappingKind::File(FileInfo::new("/app/fr/App.resources.dll"))) },
+            // flat view and loaded image of the same assembly
+            PMap { device_major: 8, device_minor: 1, inode: 600, resident_set_size_in_kibibyte: 100, ..PMap::test_page(0x70000000, "r--p", 1024, MappingKind::File(FileInfo::new("/app/App.dll"))) },
+            PMap { device_major: 8, device_minor: 1, inode: 600, resident_set_size_in_kibibyte: 200, private_dirty_in_kibibyte: 10, ..PMap::test_page(0x80000000, "r-xp", 1024, MappingKind::File(FileInfo::new("/app/App.dll"))) },
+        ]);
+        let images = LoadedImageVec::from_memory_pages(&pages);
+
+        let duplicates = DuplicateImageVec::find(&images);
+        let found = duplicates.0.iter().map(|duplicate| (duplicate.kind, duplicate.name.as_str(), duplicate.wasted_in_kibibyte())).collect::<Vec<_>>();
+        assert_eq!(found, vec![(DuplicateKind::SameName, "System.Private.CoreLib.dll", 1000), (DuplicateKind::SameFile, "Plugin.dll", 20)]);
+        assert_eq!(duplicates.wasted_in_kibibyte(), 1020);
+    }
+}
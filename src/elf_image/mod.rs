@@ -0,0 +1,473 @@
+use std::error::Error;
+use std::fmt::Display;
+
+use crate::file_info::FileInfo;
+use crate::images::{ImageSegment, LoadedImageVec, SegmentKind};
+
+// Only 64 bit little endian ELF files are supported (x86_64, aarch64), see https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.eheader.html
+// The loader maps the file by its program headers, so the file offset of a mapping tells which sections it contains.
+
+const SECTION_HEADER_SIZE: usize = 64;
+const PROGRAM_HEADER_SIZE: u64 = 56;
+const SYMBOL_SIZE: u64 = 24;
+const PAGE_SIZE: u64 = 4096;
+const PT_LOAD: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_DYNSYM: u32 = 11;
+const SHT_NOTE: u32 = 7;
+const SHT_DYNAMIC: u32 = 6;
+const SHT_NOBITS: u32 = 8;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_TLS: u64 = 0x400;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const NT_GNU_BUILD_ID: u32 = 3;
+const DT_NULL: u64 = 0;
+const DT_SONAME: u64 = 14;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ElfSection {
+    pub name: String,
+    // sh_type
+    pub kind: u32,
+    // sh_flags
+    pub flags: u64,
+    // sh_addr, virtual address of the section
+    pub address: u64,
+    pub offset: u64,
+    pub size: u64,
+    // sh_link, index of the associated section (e.g. the string table)
+    pub link: u32,
+}
+
+impl ElfSection {
+    // part of the memory image of the process
+    fn is_allocated(&self) -> bool {
+        self.flags & SHF_ALLOC != 0
+    }
+}
+
+/// Function or variable of the symbol tables
+#[derive(Debug, PartialEq, Clone)]
+pub struct ElfSymbol {
+    pub name: String,
+    // virtual address (relative to the load address for shared libraries)
+    pub address: u64,
+    pub size: u64,
+}
+
+/// Loadable segment of the program headers
+#[derive(Debug, PartialEq, Clone)]
+pub struct ElfLoadSegment {
+    pub offset: u64,
+    pub virtual_address: u64,
+    pub file_size: u64,
+}
+
+/// Identification, section layout and symbols of an ELF shared library
+#[derive(Debug, PartialEq)]
+pub struct ElfImage {
+    // GNU build-id as hex string
+    pub build_id: Option<String>,
+    pub soname: Option<String>,
+    pub sections: Vec<ElfSection>,
+    pub load_segments: Vec<ElfLoadSegment>,
+    // functions and variables of .symtab and .dynsym, ordered by address
+    pub symbols: Vec<ElfSymbol>,
+}
+
+fn read_bytes(bytes: &[u8], offset: u64, size: u64) -> Result<&[u8], Box<dyn Error>> {
+    usize::try_from(offset)
+        .ok()
+        .zip(usize::try_from(size).ok())
+        .and_then(|(offset, size)| bytes.get(offset..offset.checked_add(size)?))
+        .ok_or_else(|| format!("ELF file truncated at offset 0x{:x}", offset).into())
+}
+
+fn read_u16(bytes: &[u8], offset: u64) -> Result<u16, Box<dyn Error>> {
+    read_bytes(bytes, offset, 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: u64) -> Result<u32, Box<dyn Error>> {
+    read_bytes(bytes, offset, 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn read_u64(bytes: &[u8], offset: u64) -> Result<u64, Box<dyn Error>> {
+    read_bytes(bytes, offset, 8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+}
+
+// offset computed from values of the file, which overflows for broken files
+fn add(offset: u64, size: u64) -> Result<u64, Box<dyn Error>> {
+    offset.checked_add(size).ok_or_else(|| format!("ELF file truncated at offset 0x{:x}", offset).into())
+}
+
+// zero terminated string at the offset
+fn read_string(bytes: &[u8], offset: u64) -> Result<String, Box<dyn Error>> {
+    let tail = usize::try_from(offset)
+        .ok()
+        .and_then(|offset| bytes.get(offset..))
+        .ok_or_else(|| format!("ELF file truncated at offset 0x{:x}", offset))?;
+    let end = tail.iter().position(|byte| *byte == 0).ok_or("Unterminated string in ELF file")?;
+    Ok(String::from_utf8_lossy(&tail[..end]).to_string())
+}
+
+impl ElfImage {
+    pub fn read(file_info: &FileInfo) -> Result<Self, Box<dyn Error>> {
+        ElfImage::parse(&file_info.read()?)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if !bytes.starts_with(b"\x7fELF") {
+            return Err("Missing ELF signature".into());
+        }
+        if bytes.get(4) != Some(&2) || bytes.get(5) != Some(&1) {
+            return Err("Only 64 bit little endian ELF files are supported".into());
+        }
+        let program_headers = read_u64(bytes, 0x20)?;
+        let number_of_program_headers = read_u16(bytes, 0x38)? as u64;
+        let section_headers = read_u64(bytes, 0x28)?;
+        let number_of_sections = read_u16(bytes, 0x3c)? as u64;
+        let section_names = read_u16(bytes, 0x3e)? as u64;
+
+        let mut sections = Vec::with_capacity(number_of_sections as usize);
+        let mut name_offsets = Vec::with_capacity(number_of_sections as usize);
+        for index in 0..number_of_sections {
+            let header = add(section_headers, index * SECTION_HEADER_SIZE as u64)?;
+            name_offsets.push(read_u32(bytes, header)? as u64);
+            let section = ElfSection {
+                name: String::new(),
+                kind: read_u32(bytes, header + 4)?,
+                flags: read_u64(bytes, header + 8)?,
+                address: read_u64(bytes, header + 16)?,
+                offset: read_u64(bytes, header + 24)?,
+                size: read_u64(bytes, header + 32)?,
+                link: read_u32(bytes, header + 40)?,
+            };
+            // the ranges of the sections are used without further checks
+            add(section.offset, section.size)?;
+            add(section.address, section.size)?;
+            sections.push(section);
+        }
+        if let Some(string_table) = sections.get(section_names as usize).map(|section| section.offset) {
+            for (section, name_offset) in sections.iter_mut().zip(name_offsets) {
+                section.name = read_string(bytes, add(string_table, name_offset)?)?;
+            }
+        }
+
+        let mut load_segments = Vec::new();
+        for index in 0..number_of_program_headers {
+            let header = add(program_headers, index * PROGRAM_HEADER_SIZE)?;
+            if read_u32(bytes, header)? == PT_LOAD {
+                let segment = ElfLoadSegment {
+                    offset: read_u64(bytes, header + 8)?,
+                    virtual_address: read_u64(bytes, header + 16)?,
+                    file_size: read_u64(bytes, header + 32)?,
+                };
+                add(segment.offset, segment.file_size)?;
+                add(segment.virtual_address, segment.file_size)?;
+                load_segments.push(segment);
+            }
+        }
+
+        let mut image = ElfImage { build_id: None, soname: None, sections, load_segments, symbols: Vec::new() };
+        for section in &image.sections {
+            match section.kind {
+                SHT_NOTE if image.build_id.is_none() => image.build_id = build_id(bytes, section)?,
+                SHT_DYNAMIC => {
+                    let Some(string_table) = image.sections.get(section.link as usize) else {
+                        continue;
+                    };
+                    for entry in (section.offset..section.offset + section.size).step_by(16) {
+                        match read_u64(bytes, entry)? {
+                            DT_NULL => break,
+                            DT_SONAME => image.soname = Some(read_string(bytes, add(string_table.offset, read_u64(bytes, entry + 8)?)?)?),
+                            _ => {}
+                        }
+                    }
+                }
+                SHT_SYMTAB | SHT_DYNSYM => {
+                    let Some(string_table) = image.sections.get(section.link as usize) else {
+                        continue;
+                    };
+                    for symbol in (section.offset..section.offset + section.size).step_by(SYMBOL_SIZE as usize) {
+                        let entry = read_bytes(bytes, symbol, SYMBOL_SIZE)?;
+                        let kind = entry[4] & 0xf;
+                        let address = read_u64(entry, 8)?;
+                        if (kind == STT_FUNC || kind == STT_OBJECT) && address != 0 {
+                            image.symbols.push(ElfSymbol {
+                                name: read_string(bytes, add(string_table.offset, read_u32(entry, 0)? as u64)?)?,
+                                address,
+                                size: read_u64(entry, 16)?,
+                            });
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        // the dynamic symbols are usually part of the full symbol table too
+        image.symbols.sort_by(|a, b| a.address.cmp(&b.address).then_with(|| a.name.cmp(&b.name)));
+        image.symbols.dedup();
+        Ok(image)
+    }
+
+    /// Names of the allocated sections overlapping the file range [offset, offset + size)
+    pub fn sections_in(&self, offset: u64, size: u64) -> Vec<&str> {
+        self.sections
+            .iter()
+            .filter(|section| section.is_allocated() && section.kind != SHT_NOBITS && section.size > 0)
+            .filter(|section| section.offset < offset + size && offset < section.offset + section.size)
+            .map(|section| section.name.as_str())
+            .collect()
+    }
+
+    /// Virtual address of the file offset according to the loadable segments
+    pub fn virtual_address_of(&self, offset: u64) -> Option<u64> {
+        self.load_segments
+            .iter()
+            .find(|segment| segment.offset / PAGE_SIZE * PAGE_SIZE <= offset && offset < segment.offset + segment.file_size)
+            .and_then(|segment| segment.virtual_address.checked_add(offset)?.checked_sub(segment.offset))
+    }
+
+    /// Name of the allocated section containing the virtual address
+    pub fn section_at_virtual_address(&self, virtual_address: u64) -> Option<&str> {
+        self.sections
+            .iter()
+            .filter(|section| section.is_allocated() && section.flags & SHF_TLS == 0)
+            .find(|section| section.address <= virtual_address && virtual_address < section.address + section.size)
+            .map(|section| section.name.as_str())
+    }
+
+    /// Nearest symbol at or below the virtual address and the distance to it
+    pub fn symbol_at(&self, virtual_address: u64) -> Option<(&ElfSymbol, u64)> {
+        let count = self.symbols.partition_point(|symbol| symbol.address <= virtual_address);
+        self.symbols[..count].last().map(|symbol| (symbol, virtual_address - symbol.address))
+    }
+
+    /// Names of the zero initialized sections, which are placed in the anonymous memory behind the writable data
+    pub fn zero_initialized_sections(&self) -> Vec<&str> {
+        self.sections
+            .iter()
+            .filter(|section| section.is_allocated() && section.kind == SHT_NOBITS)
+            .map(|section| section.name.as_str())
+            .collect()
+    }
+}
+
+// GNU build-id from a note section, which may contain other notes too
+fn build_id(bytes: &[u8], section: &ElfSection) -> Result<Option<String>, Box<dyn Error>> {
+    let align = |size: u64| size.div_ceil(4) * 4;
+    let mut note = section.offset;
+    while add(note, 12)? <= section.offset + section.size {
+        let name_size = read_u32(bytes, note)? as u64;
+        let description_size = read_u32(bytes, note + 4)? as u64;
+        let kind = read_u32(bytes, note + 8)?;
+        let name = read_bytes(bytes, note + 12, name_size)?;
+        let description = add(note + 12, align(name_size))?;
+        if kind == NT_GNU_BUILD_ID && name == b"GNU\0" {
+            let id = read_bytes(bytes, description, description_size)?;
+            return Ok(Some(id.iter().map(|byte| format!("{:02x}", byte)).collect()));
+        }
+        note = add(description, align(description_size))?;
+    }
+    Ok(None)
+}
+
+/// Mapping of a native library with the ELF sections inside of its file offset range
+#[derive(Debug, PartialEq)]
+pub struct NativeLibrarySegment {
+    pub segment: ImageSegment,
+    pub sections: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct NativeLibrary {
+    pub path: String,
+    // None if the file could not be found or read
+    pub elf_image: Option<ElfImage>,
+    pub segments: Vec<NativeLibrarySegment>,
+}
+
+impl NativeLibrary {
+    pub fn private_dirty_in_kibibyte(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.segment.private_dirty_in_kibibyte).sum()
+    }
+}
+
+pub struct NativeLibraryVec(pub Vec<NativeLibrary>);
+
+impl NativeLibraryVec {
+    /// Reads the ELF files of all mapped shared libraries, the files are looked up below the sysroot, at their path or in the application folder
+    pub fn analyze(images: &LoadedImageVec, sysroot: Option<&str>, application_folder: Option<&str>) -> Self {
+        let mut libraries = Vec::new();
+        for image in images.0.iter().filter(|image| image.name().ends_with(".so") || image.name().contains(".so.")) {
+            let elf_image = FileInfo::new(&image.path)
+                .resolve(sysroot, application_folder)
+                .and_then(|file_info| ElfImage::read(&file_info).ok());
+            let segments = image.segments
+                .iter()
+                .filter(|segment| segment.kind != SegmentKind::Reserved)
+                .map(|segment| NativeLibrarySegment {
+                    segment: segment.clone(),
+                    sections: match (&elf_image, segment.kind) {
+                        (None, _) => Vec::new(),
+                        (Some(elf_image), SegmentKind::Bss) => elf_image.zero_initialized_sections(),
+                        (Some(elf_image), _) => elf_image.sections_in(segment.offset, segment.size_in_kibibyte * 1024),
+                    }.iter().map(|name| name.to_string()).collect(),
+                })
+                .collect();
+            libraries.push(NativeLibrary { path: image.path.clone(), elf_image, segments });
+        }
+        NativeLibraryVec(libraries)
+    }
+}
+
+const LIBRARY_TABLE_SEPARATOR: &str = "|----------------|----------------|------------|------------|---------------|------------------------------------------------------------------------------------------|\n";
+
+impl Display for NativeLibraryVec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut libraries = self.0.iter().filter(|library| library.elf_image.is_some()).collect::<Vec<_>>();
+        libraries.sort_by_key(|library| std::cmp::Reverse(library.private_dirty_in_kibibyte()));
+
+        for library in &libraries {
+            let elf_image = library.elf_image.as_ref().unwrap();
+            writeln!(
+                f,
+                "{} (SONAME: {}, Build-ID: {}, Symbols: {})",
+                library.path,
+                elf_image.soname.as_deref().unwrap_or("-"),
+                elf_image.build_id.as_deref().unwrap_or("-"),
+                elf_image.symbols.len())?;
+            LIBRARY_TABLE_SEPARATOR.fmt(f)?;
+            format!("| {:14} | {:14} | {:10} | {:10} | {:13} | {:88} |\n", "Segment", "Offset", "Size [KiB]", "RSS [KiB]", "Private Dirty", "Sections").fmt(f)?;
+            LIBRARY_TABLE_SEPARATOR.fmt(f)?;
+            for segment in &library.segments {
+                format!(
+                    "| {:14} | {:14x} | {:10} | {:10} | {:13} | {:88} |\n",
+                    segment.segment.kind.to_string(),
+                    segment.segment.offset,
+                    segment.segment.size_in_kibibyte,
+                    segment.segment.resident_set_size_in_kibibyte,
+                    segment.segment.private_dirty_in_kibibyte,
+                    segment.sections.join(", ")).fmt(f)?;
+            }
+            LIBRARY_TABLE_SEPARATOR.fmt(f)?;
+            writeln!(f)?;
+        }
+
+        let not_found = self.0.len() - libraries.len();
+        if not_found > 0 {
+            writeln!(f, "Native libraries not found on disk: {} (use --sysroot or --application-folder)", not_found)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod elf_image_tests {
+    use super::*;
+
+    // shared library with .text, .data, a build-id note, the SONAME in .dynamic, a .bss and two dynamic symbols
+    fn shared_library() -> Vec<u8> {
+        let mut bytes = vec![0u8; 0x700];
+        let mut write = |offset: usize, value: &[u8]| bytes[offset..offset + value.len()].copy_from_slice(value);
+        write(0, b"\x7fELF\x02\x01\x01");
+        write(0x20, &0x40u64.to_le_bytes());
+        write(0x28, &0x380u64.to_le_bytes());
+        write(0x38, &1u16.to_le_bytes());
+        write(0x3c, &9u16.to_le_bytes());
+        // loadable segment: type, offset, virtual address, file size
+        write(0x40, &PT_LOAD.to_le_bytes());
+        write(0x48, &0x0u64.to_le_bytes());
+        write(0x50, &0x10000u64.to_le_bytes());
+        write(0x60, &0x300u64.to_le_bytes());
+        write(0x3e, &7u16.to_le_bytes());
+        // note: name size, description size, type, "GNU\0", build-id
+        write(0x280, &[4, 0, 0, 0, 4, 0, 0, 0, 3, 0, 0, 0]);
+        write(0x28c, b"GNU\0\xde\xad\xbe\xef");
+        write(0x2a0, b"\0libtest.so.1\0foo\0bar\0");
+        write(0x2c0, &DT_SONAME.to_le_bytes());
+        write(0x2c8, &1u64.to_le_bytes());
+        write(0x300, b"\0.text\0.data\0.note.gnu.build-id\0.dynstr\0.dynamic\0.bss\0.shstrtab\0.dynsym\0");
+        // symbols: name, info, value, size
+        for (index, (name, info, value, size)) in [(14u32, STT_FUNC, 0x10100u64, 0x20u64), (18, STT_OBJECT, 0x10200, 0x8)].iter().enumerate() {
+            let symbol = 0x600 + (index + 1) * SYMBOL_SIZE as usize;
+            write(symbol, &name.to_le_bytes());
+            write(symbol + 4, &[*info]);
+            write(symbol + 8, &value.to_le_bytes());
+            write(symbol + 16, &size.to_le_bytes());
+        }
+        // name, type, flags, address, offset, size, link
+        let sections: [(u32, u32, u64, u64, u64, u64, u32); 8] = [
+            (1, 1, 0x6, 0x10100, 0x100, 0x100, 0),
+            (7, 1, 0x3, 0x10200, 0x200, 0x80, 0),
+            (13, SHT_NOTE, 0x2, 0x10280, 0x280, 0x14, 0),
+            (32, 3, 0x2, 0x102a0, 0x2a0, 0x16, 0),
+            (40, SHT_DYNAMIC, 0x3, 0x102c0, 0x2c0, 0x20, 4),
+            (49, SHT_NOBITS, 0x3, 0x10280, 0x280, 0x40, 0),
+            (54, 3, 0x0, 0x0, 0x300, 0x50, 0),
+            (64, SHT_DYNSYM, 0x2, 0x0, 0x600, 3 * SYMBOL_SIZE, 4),
+        ];
+        for (index, (name, kind, flags, address, offset, size, link)) in sections.iter().enumerate() {
+            let header = 0x380 + (index + 1) * SECTION_HEADER_SIZE;
+            write(header, &name.to_le_bytes());
+            write(header + 4, &kind.to_le_bytes());
+            write(header + 8, &flags.to_le_bytes());
+            write(header + 16, &address.to_le_bytes());
+            write(header + 24, &offset.to_le_bytes());
+            write(header + 32, &size.to_le_bytes());
+            write(header + 40, &link.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn build_id_soname_and_sections() {
+        let image = ElfImage::parse(&shared_library()).unwrap();
+        assert_eq!(image.build_id.as_deref(), Some("deadbeef"));
+        assert_eq!(image.soname.as_deref(), Some("libtest.so.1"));
+        assert_eq!(image.sections.len(), 9);
+        assert_eq!(image.sections_in(0x100, 0x180), vec![".text", ".data"]);
+        assert_eq!(image.sections_in(0x0, 0x100), Vec::<&str>::new());
+        assert_eq!(image.zero_initialized_sections(), vec![".bss"]);
+        // the null symbol in front is skipped
+        let symbols = image.symbols.iter().map(|symbol| (symbol.name.as_str(), symbol.address, symbol.size)).collect::<Vec<_>>();
+        assert_eq!(symbols, vec![("foo", 0x10100, 0x20), ("bar", 0x10200, 0x8)]);
+    }
+
+    #[test]
+    fn symbols_by_virtual_address() {
+        let image = ElfImage::parse(&shared_library()).unwrap();
+        assert_eq!(image.symbols.len(), 2);
+        assert_eq!(image.virtual_address_of(0x110), Some(0x10110));
+        assert_eq!(image.section_at_virtual_address(0x10110), Some(".text"));
+        let (symbol, offset) = image.symbol_at(0x10110).unwrap();
+        assert_eq!((symbol.name.as_str(), offset), ("foo", 0x10));
+        assert_eq!(image.symbol_at(0x10204).unwrap().0.name, "bar");
+        assert!(image.symbol_at(0x100).is_none());
+    }
+
+    #[test]
+    fn reject_other_files() {
+        assert!(ElfImage::parse(b"MZ").is_err());
+        assert!(ElfImage::parse(b"\x7fELF\x01\x01\x01").is_err());
+        assert!(ElfImage::parse(b"\x7fELF\x02\x01\x01").is_err());
+    }
+
+    #[test]
+    fn reject_overflowing_offsets() {
+        let section_header = |index: usize| 0x380 + index * SECTION_HEADER_SIZE;
+        // size of .dynsym
+        let mut bytes = shared_library();
+        bytes[section_header(8) + 32..section_header(8) + 40].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(ElfImage::parse(&bytes).is_err());
+        // offset of .shstrtab
+        let mut bytes = shared_library();
+        bytes[section_header(7) + 24..section_header(7) + 32].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(ElfImage::parse(&bytes).is_err());
+        // file size of the loadable segment
+        let mut bytes = shared_library();
+        bytes[0x60..0x68].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(ElfImage::parse(&bytes).is_err());
+    }
+}
@@ -19,6 +19,22 @@ impl FileInfo {
         fs::read_to_string(&self.path)
     }
 
+    pub fn read(&self) -> std::io::Result<Vec<u8>> {
+        fs::read(&self.path)
+    }
+
+    /// Locates the file of a mapping on this machine: below the root file system of the container (sysroot),
+    /// at its original path or by its name in the application folder
+    pub fn resolve(&self, sysroot: Option<&str>, application_folder: Option<&str>) -> Option<FileInfo> {
+        let relative = self.path.strip_prefix("/").unwrap_or(&self.path);
+        sysroot
+            .map(|sysroot| FileInfo::new(PathBuf::from(sysroot).join(relative)))
+            .into_iter()
+            .chain(std::iter::once(FileInfo::new(&self.path)))
+            .chain(application_folder.map(|folder| FileInfo::new(PathBuf::from(folder).join(self.name()))))
+            .find(|file_info| file_info.path.is_file())
+    }
+
     pub fn full_name(&self) -> String {
         self.path.to_str().unwrap_or("").to_string()
     }
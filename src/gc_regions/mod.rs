@@ -0,0 +1,175 @@
+use std::fmt::Display;
+
+use crate::dotnet_regions::GcReservation;
+use crate::pmap::*;
+
+// upper bounds (in KiB) of the buckets of the committed chunk size distribution,
+// 4 MiB is the basic region size and 32 MiB the large region size of the GC
+const CHUNK_SIZE_BUCKETS_IN_KIBIBYTE: [u64; 4] = [64, 1024, 4 * 1024, 32 * 1024];
+
+/// Committed part of the GC reservation, i.e. consecutive readable/writeable pages without gap
+#[derive(Debug, PartialEq, Clone)]
+pub struct CommittedChunk {
+    pub address: u64,
+    pub size_in_kibibyte: u64,
+    pub resident_set_size_in_kibibyte: u64,
+}
+
+/// Usage of the address range the GC (.NET 7+) reserves for its regions
+#[derive(Debug, PartialEq)]
+pub struct GcRegionsAnalysis {
+    pub reservation: GcReservation,
+    pub committed_in_kibibyte: u64,
+    pub resident_set_size_in_kibibyte: u64,
+    pub proportional_share_size_in_kibibyte: u64,
+    pub swap_in_kibibyte: u64,
+    // resident anonymous memory outside of the GC reservation (native allocations, stacks, runtime data structures, ...)
+    pub native_anonymous_resident_in_kibibyte: u64,
+    pub committed_chunks: Vec<CommittedChunk>,
+}
+
+impl GcRegionsAnalysis {
+    pub fn analyze(memory_pages: &PMapVec) -> Option<Self> {
+        let reservation = GcReservation::find(memory_pages)?;
+
+        let mut pages = memory_pages.0.iter().collect::<Vec<_>>();
+        pages.sort_by_key(|page| page.address);
+
+        let mut analysis = GcRegionsAnalysis {
+            reservation,
+            committed_in_kibibyte: 0,
+            resident_set_size_in_kibibyte: 0,
+            proportional_share_size_in_kibibyte: 0,
+            swap_in_kibibyte: 0,
+            native_anonymous_resident_in_kibibyte: 0,
+            committed_chunks: Vec::new(),
+        };
+
+        for page in pages {
+            if !reservation.contains(page) {
+                if matches!(page.mapping_kind, MappingKind::AnonymousPrivate(_) | MappingKind::Heap) {
+                    analysis.native_anonymous_resident_in_kibibyte += page.resident_set_size_in_kibibyte;
+                }
+                continue;
+            }
+            analysis.resident_set_size_in_kibibyte += page.resident_set_size_in_kibibyte;
+            analysis.proportional_share_size_in_kibibyte += page.proportional_share_size_in_kibibyte;
+            analysis.swap_in_kibibyte += page.swap_in_kibibyte;
+            if !page.permissions.contains(Permissions::Read) {
+                continue;
+            }
+            analysis.committed_in_kibibyte += page.size_in_kibibyte;
+            match analysis.committed_chunks.last_mut() {
+                Some(chunk) if chunk.address + chunk.size_in_kibibyte * 1024 == page.address => {
+                    chunk.size_in_kibibyte += page.size_in_kibibyte;
+                    chunk.resident_set_size_in_kibibyte += page.resident_set_size_in_kibibyte;
+                }
+                _ => analysis.committed_chunks.push(CommittedChunk {
+                    address: page.address,
+                    size_in_kibibyte: page.size_in_kibibyte,
+                    resident_set_size_in_kibibyte: page.resident_set_size_in_kibibyte,
+                }),
+            }
+        }
+
+        Some(analysis)
+    }
+
+    /// Share of committed memory (in percent) that is not part of the biggest committed chunk
+    pub fn fragmentation_in_percent(&self) -> f64 {
+        let largest = self.committed_chunks.iter().map(|chunk| chunk.size_in_kibibyte).max().unwrap_or(0);
+        if self.committed_in_kibibyte == 0 {
+            0.0
+        } else {
+            100.0 * (self.committed_in_kibibyte - largest) as f64 / self.committed_in_kibibyte as f64
+        }
+    }
+
+    /// Share of committed memory (in percent) that was never touched or was swapped out
+    pub fn committed_but_not_resident_in_percent(&self) -> f64 {
+        if self.committed_in_kibibyte == 0 {
+            0.0
+        } else {
+            100.0 * self.committed_in_kibibyte.saturating_sub(self.resident_set_size_in_kibibyte) as f64 / self.committed_in_kibibyte as f64
+        }
+    }
+
+    /// Number of committed chunks and their total size per size bucket, the last bucket has no upper bound
+    pub fn chunk_size_distribution(&self) -> Vec<(Option<u64>, usize, u64)> {
+        let mut buckets = CHUNK_SIZE_BUCKETS_IN_KIBIBYTE
+            .iter()
+            .map(|upper_bound| (Some(*upper_bound), 0, 0))
+            .collect::<Vec<_>>();
+        buckets.push((None, 0, 0));
+
+        for chunk in &self.committed_chunks {
+            let bucket = buckets
+                .iter_mut()
+                .find(|(upper_bound, ..)| upper_bound.is_none_or(|upper_bound| chunk.size_in_kibibyte <= upper_bound))
+                .unwrap();
+            bucket.1 += 1;
+            bucket.2 += chunk.size_in_kibibyte;
+        }
+        buckets
+    }
+}
+
+impl Display for GcRegionsAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Reserved Range: 0x{:x} - 0x{:x} (Confidence: {})", self.reservation.start, self.reservation.end, self.reservation.confidence)?;
+        writeln!(f, "Reserved: {} KiB", self.reservation.size_in_kibibyte())?;
+        writeln!(f, "Committed: {} KiB", self.committed_in_kibibyte)?;
+        writeln!(f, "Resident (RSS): {} KiB (PSS: {} KiB, Swap: {} KiB)", self.resident_set_size_in_kibibyte, self.proportional_share_size_in_kibibyte, self.swap_in_kibibyte)?;
+        writeln!(f, "Resident Anonymous Memory outside of the GC Reservation: {} KiB", self.native_anonymous_resident_in_kibibyte)?;
+        writeln!(f, "Committed Chunks: {}", self.committed_chunks.len())?;
+        writeln!(f, "Fragmentation (committed memory outside of the largest chunk): {:.1} %", self.fragmentation_in_percent())?;
+        writeln!(f, "Committed but not resident: {:.1} %", self.committed_but_not_resident_in_percent())?;
+        writeln!(f)?;
+
+        "|-----------------------|-----------------|------------|\n".fmt(f)?;
+        format!("| {:21} | {:15} | {:10} |\n", "Chunk Size", "#Chunks", "Size [KiB]").fmt(f)?;
+        "|-----------------------|-----------------|------------|\n".fmt(f)?;
+        let mut lower_bound = 0;
+        for (upper_bound, count, size) in self.chunk_size_distribution() {
+            let label = match upper_bound {
+                Some(upper_bound) => format!("{} - {} KiB", lower_bound, upper_bound),
+                None => format!("> {} KiB", lower_bound),
+            };
+            format!("| {:21} | {:15} | {:10} |\n", label, count, size).fmt(f)?;
+            lower_bound = upper_bound.unwrap_or(lower_bound);
+        }
+        "|-----------------------|-----------------|------------|\n".fmt(f)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod gc_regions_tests {
+    use super::*;
+
+    #[test]
+    fn committed_chunks_and_fragmentation() {
+        let start = 0x7f0000000000;
+        let mib = 1024 * 1024;
+        let pages = PMapVec(vec![
+            PMap { resident_set_size_in_kibibyte: 1024, ..PMap::test_page(start, "rw-p", 4096, MappingKind::AnonymousPrivate(None)) },
+            PMap { resident_set_size_in_kibibyte: 4096, ..PMap::test_page(start + 4 * mib, "rw-p", 4096, MappingKind::AnonymousPrivate(None)) },
+            PMap { virtual_memory_flags: VirtualMemoryFlags::DoNotIncludeInCoreDump.into(), ..PMap::test_page(start + 8 * mib, "---p", 24 * 1024, MappingKind::AnonymousPrivate(None)) },
+            PMap::test_page(start + 32 * mib, "rw-p", 8192, MappingKind::AnonymousPrivate(None)),
+            PMap { virtual_memory_flags: VirtualMemoryFlags::DoNotIncludeInCoreDump.into(), ..PMap::test_page(start + 40 * mib, "---p", 256 * 1024 * 1024 - 40 * 1024, MappingKind::AnonymousPrivate(None)) },
+            PMap { resident_set_size_in_kibibyte: 100, ..PMap::test_page(start + 256 * 1024 * mib, "rw-p", 100, MappingKind::AnonymousPrivate(None)) },
+        ]);
+
+        let analysis = GcRegionsAnalysis::analyze(&pages).unwrap();
+        assert_eq!(analysis.reservation.size_in_kibibyte(), 256 * 1024 * 1024);
+        assert_eq!(analysis.committed_in_kibibyte, 16384);
+        assert_eq!(analysis.resident_set_size_in_kibibyte, 5120);
+        assert_eq!(analysis.native_anonymous_resident_in_kibibyte, 100);
+        assert_eq!(analysis.committed_chunks.len(), 2);
+        assert_eq!(analysis.committed_chunks[0].size_in_kibibyte, 8192);
+        assert_eq!(analysis.fragmentation_in_percent(), 50.0);
+
+        let distribution = analysis.chunk_size_distribution();
+        assert_eq!(distribution[3], (Some(32 * 1024), 2, 16384));
+    }
+}
@@ -0,0 +1,245 @@
+use std::fmt::Display;
+use std::path::Path;
+
+use crate::dotnet_regions::DotnetRegionKind;
+use crate::pmap::*;
+use crate::pmap_analyzer::{PMapCategory, PMapCategoryVec};
+
+// PMD size on x86_64, used if hpage_pmd_size can't be read
+const DEFAULT_HUGE_PAGE_SIZE_IN_KIBIBYTE: u64 = 2048;
+// huge pages only pay off for big heaps, below this RSS the GC heap gets no recommendation
+const MIN_GC_HEAP_FOR_RECOMMENDATION_IN_KIBIBYTE: u64 = 256 * 1024;
+// share of the GC heap that should be backed by huge pages if they are used
+const MIN_HUGE_PAGE_SHARE_IN_PERCENT: f64 = 50.0;
+
+/// Mode of transparent huge pages of the system (the selected value in brackets, e.g. `always [madvise] never`)
+#[derive(Debug, PartialEq, Clone)]
+pub struct TransparentHugePageSettings {
+    // always, madvise or never
+    pub enabled: Option<String>,
+    pub defrag: Option<String>,
+    pub huge_page_size_in_kibibyte: u64,
+    pub source: String,
+}
+
+fn selected_value(content: &str) -> Option<String> {
+    let start = content.find('[')?;
+    let end = content[start..].find(']')? + start;
+    Some(content[start + 1..end].to_string())
+}
+
+impl TransparentHugePageSettings {
+    /// Reads `enabled`, `defrag` and `hpage_pmd_size` of a folder like `/sys/kernel/mm/transparent_hugepage`,
+    /// a single file is read as the content of `enabled`
+    pub fn read(path: &str) -> Self {
+        let path = Path::new(path);
+        let read = |path: &Path| std::fs::read_to_string(path).ok();
+        let (enabled, defrag, huge_page_size) = if path.is_dir() {
+            (read(&path.join("enabled")), read(&path.join("defrag")), read(&path.join("hpage_pmd_size")))
+        } else {
+            (read(path), None, None)
+        };
+        TransparentHugePageSettings {
+            enabled: enabled.as_deref().and_then(selected_value),
+            defrag: defrag.as_deref().and_then(selected_value),
+            huge_page_size_in_kibibyte: huge_page_size
+                .and_then(|size| size.trim().parse::<u64>().ok())
+                .map(|size| size / 1024)
+                .unwrap_or(DEFAULT_HUGE_PAGE_SIZE_IN_KIBIBYTE),
+            source: path.display().to_string(),
+        }
+    }
+}
+
+/// Huge page usage of a category
+#[derive(Debug, PartialEq)]
+pub struct CategoryHugePages {
+    pub name: String,
+    pub resident_set_size_in_kibibyte: u64,
+    // RSS of the memory pages flagged THPeligible
+    pub eligible_resident_set_size_in_kibibyte: u64,
+    // AnonHugePages, ShmemPmdMapped and FilePmdMapped
+    pub huge_in_kibibyte: u64,
+    // memory pages advised with MADV_HUGEPAGE (hg) or MADV_NOHUGEPAGE (nh)
+    pub advised_pages: usize,
+    pub not_advised_pages: usize,
+}
+
+impl CategoryHugePages {
+    fn new(category: &PMapCategory) -> Self {
+        let pages = &category.pages;
+        CategoryHugePages {
+            name: category.name.clone(),
+            resident_set_size_in_kibibyte: category.resident_set_size_in_kibibyte,
+            eligible_resident_set_size_in_kibibyte: pages
+                .iter()
+                .filter(|page| page.transparent_huge_page_eligible)
+                .map(|page| page.resident_set_size_in_kibibyte)
+                .sum(),
+            huge_in_kibibyte: pages
+                .iter()
+                .map(|page| page.anonymous_huge_pages_in_kibibyte + page.shared_memory_associated_with_huge_pages_in_kibibyte + page.file_pme_mapped_in_kibibyte)
+                .sum(),
+            advised_pages: pages.iter().filter(|page| page.virtual_memory_flags.contains(VirtualMemoryFlags::HugePageAdvise)).count(),
+            not_advised_pages: pages.iter().filter(|page| page.virtual_memory_flags.contains(VirtualMemoryFlags::NoHugePageAdvise)).count(),
+        }
+    }
+
+    /// Share of the RSS backed by huge pages
+    pub fn huge_in_percent(&self) -> f64 {
+        if self.resident_set_size_in_kibibyte == 0 {
+            0.0
+        } else {
+            100.0 * self.huge_in_kibibyte as f64 / self.resident_set_size_in_kibibyte as f64
+        }
+    }
+}
+
+/// Effectiveness of transparent huge pages per category
+pub struct HugePagesAnalysis {
+    pub settings: TransparentHugePageSettings,
+    // categories with eligible, huge or advised memory ordered by the eligible RSS
+    pub categories: Vec<CategoryHugePages>,
+    pub gc_heap: Option<CategoryHugePages>,
+}
+
+impl HugePagesAnalysis {
+    pub fn analyze(categories: &PMapCategoryVec, settings: TransparentHugePageSettings) -> Self {
+        let gc_heap_name = DotnetRegionKind::GcHeap.to_string();
+        let mut huge_pages = categories.0
+            .iter()
+            .map(CategoryHugePages::new)
+            .filter(|category| category.eligible_resident_set_size_in_kibibyte > 0 || category.huge_in_kibibyte > 0 || category.advised_pages > 0)
+            .collect::<Vec<_>>();
+        huge_pages.sort_by_key(|category| std::cmp::Reverse(category.eligible_resident_set_size_in_kibibyte));
+        HugePagesAnalysis {
+            settings,
+            categories: huge_pages,
+            gc_heap: categories.0.iter().find(|category| category.name == gc_heap_name).map(CategoryHugePages::new),
+        }
+    }
+
+    /// Hints when huge pages would reduce the TLB misses of a big GC heap or bloat the thread stacks
+    pub fn recommendations(&self) -> Vec<String> {
+        let mut recommendations = Vec::new();
+        let thread_stacks_name = DotnetRegionKind::ThreadStack.to_string();
+        let thread_stacks = self.categories.iter().find(|category| category.name == thread_stacks_name && category.huge_in_kibibyte > 0);
+        if let (Some(thread_stacks), None | Some("always")) = (thread_stacks, self.settings.enabled.as_deref()) {
+            recommendations.push(format!(
+                "{} KiB of the thread stacks are backed by huge pages, which makes barely used stacks fully resident: set THP to madvise",
+                thread_stacks.huge_in_kibibyte));
+        }
+        let Some(gc_heap) = &self.gc_heap else {
+            return recommendations;
+        };
+        if gc_heap.resident_set_size_in_kibibyte < MIN_GC_HEAP_FOR_RECOMMENDATION_IN_KIBIBYTE || gc_heap.huge_in_percent() >= MIN_HUGE_PAGE_SHARE_IN_PERCENT {
+            return recommendations;
+        }
+        let large_pages = "DOTNET_GCLargePages=1 (together with DOTNET_GCHeapHardLimit and huge pages reserved with vm.nr_hugepages)";
+        match self.settings.enabled.as_deref() {
+            Some("never") => recommendations.push(format!(
+                "THP is disabled, but the GC heap has {} KiB resident: set {}/enabled to madvise (or always) or use {}",
+                gc_heap.resident_set_size_in_kibibyte, self.settings.source, large_pages)),
+            Some("madvise") if gc_heap.advised_pages == 0 => recommendations.push(format!(
+                "THP is only used for advised memory and the GC heap ({} KiB resident) is not advised: use {} or set THP to always",
+                gc_heap.resident_set_size_in_kibibyte, large_pages)),
+            Some(_) => recommendations.push(format!(
+                "Only {:.1} % of the GC heap are backed by huge pages: check the defrag setting ({}) and khugepaged or use {}",
+                gc_heap.huge_in_percent(), self.settings.defrag.as_deref().unwrap_or("unknown"), large_pages)),
+            None => recommendations.push(format!(
+                "The THP mode is unknown ({} not readable), huge pages would pay off for the GC heap ({} KiB resident)",
+                self.settings.source, gc_heap.resident_set_size_in_kibibyte)),
+        }
+        recommendations
+    }
+}
+
+const HUGE_PAGES_TABLE_SEPARATOR: &str = "|----------------------------------------------------------|------------|------------------|------------------|------------|--------------|--------------|\n";
+
+impl Display for HugePagesAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "THP Mode: {} (Defrag: {}, Huge Page Size: {} KiB, from {})",
+            self.settings.enabled.as_deref().unwrap_or("unknown"),
+            self.settings.defrag.as_deref().unwrap_or("unknown"),
+            self.settings.huge_page_size_in_kibibyte,
+            self.settings.source)?;
+        match &self.gc_heap {
+            Some(gc_heap) => writeln!(
+                f,
+                "GC Heap: RSS {} KiB, eligible {} KiB, backed by huge pages {} KiB ({:.1} %), advised memory pages: {}",
+                gc_heap.resident_set_size_in_kibibyte,
+                gc_heap.eligible_resident_set_size_in_kibibyte,
+                gc_heap.huge_in_kibibyte,
+                gc_heap.huge_in_percent(),
+                gc_heap.advised_pages)?,
+            None => writeln!(f, "GC Heap: not found")?,
+        }
+        writeln!(f)?;
+
+        HUGE_PAGES_TABLE_SEPARATOR.fmt(f)?;
+        format!(
+            "| {:56} | {:10} | {:16} | {:16} | {:10} | {:12} | {:12} |\n",
+            "Category", "RSS [KiB]", "Eligible [KiB]", "Huge Pages [KiB]", "Huge [%]", "#Advised", "#Not Advised").fmt(f)?;
+        HUGE_PAGES_TABLE_SEPARATOR.fmt(f)?;
+        for category in &self.categories {
+            format!(
+                "| {:56} | {:10} | {:16} | {:16} | {:10.1} | {:12} | {:12} |\n",
+                category.name,
+                category.resident_set_size_in_kibibyte,
+                category.eligible_resident_set_size_in_kibibyte,
+                category.huge_in_kibibyte,
+                category.huge_in_percent(),
+                category.advised_pages,
+                category.not_advised_pages).fmt(f)?;
+        }
+        HUGE_PAGES_TABLE_SEPARATOR.fmt(f)?;
+        for recommendation in self.recommendations() {
+            writeln!(f, "Recommendation: {}", recommendation)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod huge_pages_tests {
+    use super::*;
+    use crate::pmap_analyzer::CategorySortKey;
+
+    fn settings(enabled: &str) -> TransparentHugePageSettings {
+        TransparentHugePageSettings {
+            enabled: selected_value(enabled),
+            defrag: None,
+            huge_page_size_in_kibibyte: 2048,
+            source: "/sys/kernel/mm/transparent_hugepage".to_string(),
+        }
+    }
+
+    #[test]
+    fn eligible_and_huge_memory_of_gc_heap() {
+        assert_eq!(selected_value("always [madvise] never\n"), Some("madvise".to_string()));
+
+        let page = |resident_set_size_in_kibibyte: u64, anonymous_huge_pages_in_kibibyte: u64, transparent_huge_page_eligible: bool| PMap {
+            size_in_kibibyte: resident_set_size_in_kibibyte,
+            resident_set_size_in_kibibyte,
+            anonymous_huge_pages_in_kibibyte,
+            transparent_huge_page_eligible,
+            mapping_kind: MappingKind::AnonymousPrivate(None),
+            ..Default::default()
+        };
+        let pages = PMapVec(vec![page(512 * 1024, 4096, true), page(64, 0, false)]);
+        let categories = PMapCategory::get_categories_from_memory_pages(
+            pages,
+            &|page| if page.size_in_kibibyte > 64 { DotnetRegionKind::GcHeap.to_string() } else { "Anonymous".to_string() },
+            CategorySortKey::Pss).unwrap();
+
+        let analysis = HugePagesAnalysis::analyze(&categories, settings("always [madvise] never"));
+        assert_eq!(analysis.categories.len(), 1);
+        let gc_heap = analysis.gc_heap.as_ref().unwrap();
+        assert_eq!((gc_heap.eligible_resident_set_size_in_kibibyte, gc_heap.huge_in_kibibyte), (512 * 1024, 4096));
+        assert!(analysis.recommendations()[0].contains("is not advised"));
+
+        assert!(HugePagesAnalysis::analyze(&categories, settings("always madvise [never]")).recommendations()[0].starts_with("THP is disabled"));
+    }
+}
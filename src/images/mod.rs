@@ -0,0 +1,339 @@
+use std::fmt::Display;
+
+use crate::pmap::*;
+
+// The dynamic loader (and the PE loader of the runtime) maps every image segment by segment, e.g. libc.so.6:
+// 7fafe181f000 r--p 00000000 08:01 785412   152  <- ELF header, dynamic symbols, relocations
+// 7fafe1845000 r-xp 00026000 08:01 785412  1364  <- .text
+// 7fafe199a000 r--p 0017b000 08:01 785412   332  <- .rodata
+// 7fafe19ed000 r--p 001ce000 08:01 785412    16  <- .data.rel.ro (read-only after the relocation)
+// 7fafe19f1000 rw-p 001d2000 08:01 785412     8  <- .data
+// 7fafe19f3000 rw-p 00000000 00:00      0    52  <- .bss
+// The segments of an image may be separated by inaccessible (---p) mappings used for the alignment.
+
+/// Part of a loaded image
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SegmentKind {
+    // file headers at offset 0 in front of the other segments
+    Headers,
+    // executable segment
+    Code,
+    // read-only segment (constants, read-only after relocation)
+    ReadOnlyData,
+    // writable file-backed segment
+    WritableData,
+    // anonymous memory directly following the writable data (zero initialized variables)
+    Bss,
+    // inaccessible memory between the segments
+    Reserved,
+}
+
+impl Display for SegmentKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SegmentKind::Headers => "Headers".fmt(f),
+            SegmentKind::Code => "Code".fmt(f),
+            SegmentKind::ReadOnlyData => "Read-Only Data".fmt(f),
+            SegmentKind::WritableData => "Writable Data".fmt(f),
+            SegmentKind::Bss => ".bss".fmt(f),
+            SegmentKind::Reserved => "Reserved".fmt(f),
+        }
+    }
+}
+
+/// One memory page of a loaded image
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImageSegment {
+    pub address: u64,
+    // offset in the image file (0 for the anonymous segments)
+    pub offset: u64,
+    pub size_in_kibibyte: u64,
+    pub resident_set_size_in_kibibyte: u64,
+    pub private_dirty_in_kibibyte: u64,
+    pub kind: SegmentKind,
+}
+
+impl ImageSegment {
+    fn new(page: &PMap, kind: SegmentKind) -> Self {
+        Self {
+            address: page.address,
+            offset: page.offset,
+            size_in_kibibyte: page.size_in_kibibyte,
+            resident_set_size_in_kibibyte: page.resident_set_size_in_kibibyte,
+            private_dirty_in_kibibyte: page.private_dirty_in_kibibyte,
+            kind,
+        }
+    }
+
+    /// First address after the segment
+    pub fn end_address(&self) -> u64 {
+        self.address + self.size_in_kibibyte * 1024
+    }
+}
+
+/// Shared library, executable or assembly with all of its consecutive mappings
+#[derive(Debug, PartialEq, Clone)]
+pub struct LoadedImage {
+    pub path: String,
+    pub device_major: u16,
+    pub device_minor: u16,
+    pub inode: u64,
+    // ordered by address
+    pub segments: Vec<ImageSegment>,
+}
+
+impl LoadedImage {
+    fn new(page: &PMap, path: String) -> Self {
+        Self {
+            path,
+            device_major: page.device_major,
+            device_minor: page.device_minor,
+            inode: page.inode,
+            segments: vec![ImageSegment::new(page, file_segment_kind(page))],
+        }
+    }
+
+    /// File name of the image
+    pub fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    pub fn start_address(&self) -> u64 {
+        self.segments.first().map(|segment| segment.address).unwrap_or(0)
+    }
+
+    /// First address after the image (including the .bss)
+    pub fn end_address(&self) -> u64 {
+        self.segments.last().map(|segment| segment.end_address()).unwrap_or(0)
+    }
+
+    /// Segment containing the address
+    pub fn segment_at(&self, address: u64) -> Option<&ImageSegment> {
+        self.segments.iter().find(|segment| segment.address <= address && address < segment.end_address())
+    }
+
+    pub fn size_in_kibibyte(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.size_in_kibibyte).sum()
+    }
+
+    pub fn resident_set_size_in_kibibyte(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.resident_set_size_in_kibibyte).sum()
+    }
+
+    pub fn size_of(&self, kind: SegmentKind) -> u64 {
+        self.segments.iter().filter(|segment| segment.kind == kind).map(|segment| segment.size_in_kibibyte).sum()
+    }
+
+    pub fn resident_set_size_of(&self, kind: SegmentKind) -> u64 {
+        self.segments.iter().filter(|segment| segment.kind == kind).map(|segment| segment.resident_set_size_in_kibibyte).sum()
+    }
+
+    /// RSS of the executable segments
+    pub fn code_resident_set_size_in_kibibyte(&self) -> u64 {
+        self.resident_set_size_of(SegmentKind::Code)
+    }
+
+    /// RSS of headers, read-only and writable data including the .bss
+    pub fn data_resident_set_size_in_kibibyte(&self) -> u64 {
+        self.resident_set_size_in_kibibyte() - self.code_resident_set_size_in_kibibyte()
+    }
+
+    /// Private dirty memory of all segments including the .bss
+    pub fn private_dirty_in_kibibyte(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.private_dirty_in_kibibyte).sum()
+    }
+
+    /// Copy-on-write copies of the file-backed data, which are caused by the relocations of the loader
+    /// (and by writes to initialized variables)
+    pub fn relocation_private_dirty_in_kibibyte(&self) -> u64 {
+        self.segments
+            .iter()
+            .filter(|segment| matches!(segment.kind, SegmentKind::Headers | SegmentKind::ReadOnlyData | SegmentKind::WritableData))
+            .map(|segment| segment.private_dirty_in_kibibyte)
+            .sum()
+    }
+
+    fn is_same_file(&self, page: &PMap) -> bool {
+        self.device_major == page.device_major && self.device_minor == page.device_minor && self.inode == page.inode
+    }
+
+    // the headers are only a separate segment if the image consists of more than one file-backed mapping
+    fn finish(mut self) -> Self {
+        let file_segments = self.segments.iter().filter(|segment| segment.kind != SegmentKind::Bss).count();
+        if let Some(first) = self.segments.first_mut() {
+            if first.kind == SegmentKind::ReadOnlyData && first.offset == 0 && file_segments > 1 {
+                first.kind = SegmentKind::Headers;
+            }
+        }
+        self
+    }
+}
+
+fn file_segment_kind(page: &PMap) -> SegmentKind {
+    if page.permissions.contains(Permissions::Execute) {
+        SegmentKind::Code
+    } else if page.permissions.contains(Permissions::Write) {
+        SegmentKind::WritableData
+    } else if page.permissions.contains(Permissions::Read) {
+        SegmentKind::ReadOnlyData
+    } else {
+        SegmentKind::Reserved
+    }
+}
+
+pub struct LoadedImageVec(pub Vec<LoadedImage>);
+
+impl LoadedImageVec {
+    /// Groups consecutive mappings of the same file (device and inode) into loaded images, ordered by address
+    pub fn from_memory_pages(memory_pages: &PMapVec) -> Self {
+        let mut pages = memory_pages.0.iter().collect::<Vec<_>>();
+        pages.sort_by_key(|page| page.address);
+
+        let mut images = Vec::new();
+        let mut current: Option<LoadedImage> = None;
+        // inaccessible anonymous pages after the current image, part of it if the image continues behind them
+        let mut gap: Vec<&PMap> = Vec::new();
+
+        for page in pages {
+            let adjacent = current.as_ref().is_some_and(|image| {
+                gap.last().map(|gap_page| gap_page.end_address()).unwrap_or(image.end_address()) == page.address
+            });
+            match &page.mapping_kind {
+                MappingKind::File(file_info) if !page.mapping_kind.is_doublemapper() => {
+                    match current.as_mut() {
+                        Some(image) if adjacent && image.is_same_file(page) => {
+                            image.segments.extend(gap.drain(..).map(|gap_page| ImageSegment::new(gap_page, SegmentKind::Reserved)));
+                            image.segments.push(ImageSegment::new(page, file_segment_kind(page)));
+                        }
+                        _ => {
+                            images.extend(current.take().map(LoadedImage::finish));
+                            gap.clear();
+                            current = Some(LoadedImage::new(page, file_info.full_name()));
+                        }
+                    }
+                }
+                MappingKind::AnonymousPrivate(None) if adjacent => {
+                    let image = current.as_mut().unwrap();
+                    if !page.permissions.contains(Permissions::Read) {
+                        gap.push(page);
+                    } else if gap.is_empty()
+                        && page.permissions.contains(Permissions::Write)
+                        && image.segments.last().is_some_and(|segment| segment.kind == SegmentKind::WritableData) {
+                        image.segments.push(ImageSegment::new(page, SegmentKind::Bss));
+                        images.extend(current.take().map(LoadedImage::finish));
+                    } else {
+                        images.extend(current.take().map(LoadedImage::finish));
+                        gap.clear();
+                    }
+                }
+                _ => {
+                    images.extend(current.take().map(LoadedImage::finish));
+                    gap.clear();
+                }
+            }
+        }
+        images.extend(current.take().map(LoadedImage::finish));
+
+        LoadedImageVec(images)
+    }
+
+    /// Image and segment containing the address
+    pub fn find(&self, address: u64) -> Option<(&LoadedImage, &ImageSegment)> {
+        let count = self.0.partition_point(|image| image.start_address() <= address);
+        let image = self.0[..count].last()?;
+        image.segment_at(address).map(|segment| (image, segment))
+    }
+}
+
+const IMAGE_TABLE_SEPARATOR: &str = "|----------------------------------------------------------|------------|------------|------------|------------|------------|------------|------------|------------|------------|------------|---------------|\n";
+
+impl Display for LoadedImageVec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut images = self.0.iter().collect::<Vec<_>>();
+        images.sort_by_key(|image| std::cmp::Reverse(image.resident_set_size_in_kibibyte()));
+
+        IMAGE_TABLE_SEPARATOR.fmt(f)?;
+        format!(
+            "| {:56} | {:10} | {:10} | {:10} | {:10} | {:10} | {:10} | {:10} | {:10} | {:10} | {:10} | {:13} |\n",
+            "Image", "#Mappings", "Size [KiB]", "RSS [KiB]", "Headers", "Code", "RO Data", "RW Data", ".bss", "Code RSS", "Data RSS", "Reloc. Dirty").fmt(f)?;
+        IMAGE_TABLE_SEPARATOR.fmt(f)?;
+        for image in images {
+            format!(
+                "| {:56} | {:10} | {:10} | {:10} | {:10} | {:10} | {:10} | {:10} | {:10} | {:10} | {:10} | {:13} |\n",
+                image.name(),
+                image.segments.len(),
+                image.size_in_kibibyte(),
+                image.resident_set_size_in_kibibyte(),
+                image.size_of(SegmentKind::Headers),
+                image.size_of(SegmentKind::Code),
+                image.size_of(SegmentKind::ReadOnlyData),
+                image.size_of(SegmentKind::WritableData),
+                image.size_of(SegmentKind::Bss),
+                image.code_resident_set_size_in_kibibyte(),
+                image.data_resident_set_size_in_kibibyte(),
+                image.relocation_private_dirty_in_kibibyte()).fmt(f)?;
+        }
+        IMAGE_TABLE_SEPARATOR.fmt(f)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod images_tests {
+    use super::*;
+    use crate::file_info::FileInfo;
+
+    #[test]
+    fn elf_segments_with_bss() {
+        let library = |inode: u64| MappingKind::File(FileInfo::new(format!("/usr/lib/lib{}.so", inode)));
+        let pages = PMapVec(vec![
+            PMap { device_major: 8, device_minor: 1, inode: 785412, resident_set_size_in_kibibyte: 152, ..PMap::test_page(0x7fafe181f000, "r--p", 152, library(785412)) },
+            PMap { offset: 0x26000, device_major: 8, device_minor: 1, inode: 785412, resident_set_size_in_kibibyte: 1364, ..PMap::test_page(0x7fafe1845000, "r-xp", 1364, library(785412)) },
+            PMap { offset: 0x17b000, device_major: 8, device_minor: 1, inode: 785412, resident_set_size_in_kibibyte: 332, ..PMap::test_page(0x7fafe199a000, "r--p", 332, library(785412)) },
+            PMap { offset: 0x1ce000, device_major: 8, device_minor: 1, inode: 785412, resident_set_size_in_kibibyte: 16, private_dirty_in_kibibyte: 16, ..PMap::test_page(0x7fafe19ed000, "r--p", 16, library(785412)) },
+            PMap { offset: 0x1d2000, device_major: 8, device_minor: 1, inode: 785412, resident_set_size_in_kibibyte: 8, private_dirty_in_kibibyte: 8, ..PMap::test_page(0x7fafe19f1000, "rw-p", 8, library(785412)) },
+            PMap { resident_set_size_in_kibibyte: 52, private_dirty_in_kibibyte: 20, ..PMap::test_page(0x7fafe19f3000, "rw-p", 52, MappingKind::AnonymousPrivate(None)) },
+            // heap of the application, not part of the image
+            PMap { resident_set_size_in_kibibyte: 1024, private_dirty_in_kibibyte: 1024, ..PMap::test_page(0x7fafe1a00000, "rw-p", 1024, MappingKind::AnonymousPrivate(None)) },
+        ]);
+
+        let images = LoadedImageVec::from_memory_pages(&pages);
+        assert_eq!(images.0.len(), 1);
+        let image = &images.0[0];
+        assert_eq!(image.name(), "lib785412.so");
+        assert_eq!(image.segments.len(), 6);
+        assert_eq!(image.size_of(SegmentKind::Headers), 152);
+        assert_eq!(image.size_of(SegmentKind::Code), 1364);
+        assert_eq!(image.size_of(SegmentKind::ReadOnlyData), 348);
+        assert_eq!(image.size_of(SegmentKind::Bss), 52);
+        assert_eq!(image.code_resident_set_size_in_kibibyte(), 1364);
+        assert_eq!(image.data_resident_set_size_in_kibibyte(), 152 + 348 + 8 + 52);
+        assert_eq!(image.relocation_private_dirty_in_kibibyte(), 24);
+    }
+
+    #[test]
+    fn segments_separated_by_reserved_memory() {
+        let library = |inode: u64| MappingKind::File(FileInfo::new(format!("/usr/lib/lib{}.so", inode)));
+        let pages = PMapVec(vec![
+            PMap { device_major: 8, device_minor: 1, inode: 916305, resident_set_size_in_kibibyte: 4, ..PMap::test_page(0x7faf63f50000, "r--p", 4, library(916305)) },
+            PMap { resident_set_size_in_kibibyte: 60, ..PMap::test_page(0x7faf63f51000, "---p", 60, MappingKind::AnonymousPrivate(None)) },
+            PMap { device_major: 8, device_minor: 1, inode: 916305, resident_set_size_in_kibibyte: 336, ..PMap::test_page(0x7faf63f60000, "r-xp", 336, library(916305)) },
+            PMap { resident_set_size_in_kibibyte: 124, ..PMap::test_page(0x7faf63fb4000, "---p", 124, MappingKind::AnonymousPrivate(None)) },
+            PMap { offset: 0x53000, device_major: 8, device_minor: 1, inode: 916305, resident_set_size_in_kibibyte: 144, private_dirty_in_kibibyte: 16, ..PMap::test_page(0x7faf63fd3000, "rw-p", 144, library(916305)) },
+            PMap { resident_set_size_in_kibibyte: 124, ..PMap::test_page(0x7faf63ff7000, "---p", 124, MappingKind::AnonymousPrivate(None)) },
+            PMap { offset: 0x76000, device_major: 8, device_minor: 1, inode: 916305, resident_set_size_in_kibibyte: 4, ..PMap::test_page(0x7faf64016000, "r--p", 4, library(916305)) },
+            PMap { resident_set_size_in_kibibyte: 36, ..PMap::test_page(0x7faf64017000, "---p", 36, MappingKind::AnonymousPrivate(None)) },
+            PMap { device_major: 8, device_minor: 1, inode: 916306, resident_set_size_in_kibibyte: 144, ..PMap::test_page(0x7faf64020000, "r--s", 144, library(916306)) },
+        ]);
+
+        let images = LoadedImageVec::from_memory_pages(&pages);
+        assert_eq!(images.0.len(), 2);
+        assert_eq!(images.0[0].segments.len(), 7);
+        assert_eq!(images.0[0].size_of(SegmentKind::Reserved), 308);
+        assert_eq!(images.0[0].end_address(), 0x7faf64017000);
+        assert_eq!(images.find(0x7faf63f70000).unwrap().1.kind, SegmentKind::Code);
+        assert!(images.find(0x7faf64017000).is_none());
+        // a single mapping of the whole file has no separate headers
+        assert_eq!(images.0[1].segments[0].kind, SegmentKind::ReadOnlyData);
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use crate::pmap::*;
+
+// With W^X enabled (default since .NET 7) the runtime puts JIT code and stubs into a memfd (`doublemapper`)
+// and maps the same file offsets twice: executable (r-x) for running the code and writable (rw-) for the JIT
+// and stub generation. Both views are listed by pmap, so summing up the views counts the memory twice.
+
+/// State of the W^X (write xor execute) protection of the runtime
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum WriteXorExecute {
+    // JIT code lives in doublemapper views
+    Enabled,
+    // JIT code lives in anonymous read/write/execute memory (DOTNET_EnableWriteXorExecute=0)
+    Disabled,
+    // neither doublemapper nor read/write/execute memory found (e.g. not a .NET process)
+    #[default]
+    Unknown,
+}
+
+impl Display for WriteXorExecute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteXorExecute::Enabled => "Enabled".fmt(f),
+            WriteXorExecute::Disabled => "Disabled".fmt(f),
+            WriteXorExecute::Unknown => "Unknown".fmt(f),
+        }
+    }
+}
+
+/// Executable memory of the JIT, with the views of the doublemapper paired by file offset
+#[derive(Debug, PartialEq, Default)]
+pub struct JitCodeAnalysis {
+    pub write_xor_execute: WriteXorExecute,
+    // number of doublemapper views
+    pub mapping_count: usize,
+    // size of the executable (r-x) views
+    pub code_views_in_kibibyte: u64,
+    // size of the writable (rw-) views
+    pub writable_views_in_kibibyte: u64,
+    // size of the views without access rights (reserved)
+    pub reserved_views_in_kibibyte: u64,
+    // size of the memfd behind all views, each file offset counted once
+    pub unique_in_kibibyte: u64,
+    // size of the executable views, each file offset counted once
+    pub executable_in_kibibyte: u64,
+    // code that is currently mapped executable and writable at the same time (open write window)
+    pub writable_code_in_kibibyte: u64,
+    // number of writable views that map (parts of) the same file offsets as an executable view
+    pub mapping_pairs: usize,
+    // RSS of all views
+    pub resident_set_size_in_kibibyte: u64,
+    // RSS with the double mapped pages counted once
+    pub unique_resident_set_size_in_kibibyte: u64,
+    // anonymous memory that is readable, writeable and executable
+    pub read_write_execute_anonymous_in_kibibyte: u64,
+    pub read_write_execute_anonymous_count: usize,
+}
+
+// view of a memfd file range [start, end) in bytes
+struct View {
+    start: u64,
+    end: u64,
+    resident_set_size_in_kibibyte: u64,
+}
+
+impl JitCodeAnalysis {
+    pub fn analyze(memory_pages: &PMapVec) -> Self {
+        let mut analysis = JitCodeAnalysis::default();
+        // views grouped by backing memfd (device and inode)
+        let mut executable: BTreeMap<(u16, u16, u64), Vec<View>> = BTreeMap::new();
+        let mut writable: BTreeMap<(u16, u16, u64), Vec<View>> = BTreeMap::new();
+        let mut all: BTreeMap<(u16, u16, u64), Vec<View>> = BTreeMap::new();
+
+        for page in &memory_pages.0 {
+            if !page.mapping_kind.is_doublemapper() {
+                if matches!(page.mapping_kind, MappingKind::AnonymousPrivate(_) | MappingKind::AnonymousShared(_))
+                    && page.permissions.contains(Permissions::Read | Permissions::Write | Permissions::Execute) {
+                    analysis.read_write_execute_anonymous_in_kibibyte += page.size_in_kibibyte;
+                    analysis.read_write_execute_anonymous_count += 1;
+                }
+                continue;
+            }
+
+            analysis.mapping_count += 1;
+            analysis.resident_set_size_in_kibibyte += page.resident_set_size_in_kibibyte;
+            let key = (page.device_major, page.device_minor, page.inode);
+            let view = || View {
+                start: page.offset,
+                end: page.offset + page.size_in_kibibyte * 1024,
+                resident_set_size_in_kibibyte: page.resident_set_size_in_kibibyte,
+            };
+            if page.permissions.contains(Permissions::Execute) {
+                analysis.code_views_in_kibibyte += page.size_in_kibibyte;
+                executable.entry(key).or_default().push(view());
+            } else if page.permissions.contains(Permissions::Write) {
+                analysis.writable_views_in_kibibyte += page.size_in_kibibyte;
+                writable.entry(key).or_default().push(view());
+            } else {
+                analysis.reserved_views_in_kibibyte += page.size_in_kibibyte;
+            }
+            all.entry(key).or_default().push(view());
+        }
+
+        for views in all.values_mut() {
+            analysis.unique_in_kibibyte += merged_size(views) / 1024;
+        }
+        for views in executable.values_mut() {
+            analysis.executable_in_kibibyte += merged_size(views) / 1024;
+        }
+
+        analysis.unique_resident_set_size_in_kibibyte = analysis.resident_set_size_in_kibibyte;
+        for (key, writable_views) in &writable {
+            let Some(executable_views) = executable.get_mut(key) else {
+                continue;
+            };
+            executable_views.sort_by_key(|view| view.start);
+            for view in writable_views {
+                let first = executable_views.partition_point(|executable_view| executable_view.end <= view.start);
+                let overlap: u64 = executable_views[first..]
+                    .iter()
+                    .take_while(|executable_view| executable_view.start < view.end)
+                    .map(|executable_view| executable_view.end.min(view.end) - executable_view.start.max(view.start))
+                    .sum();
+                if overlap > 0 {
+                    analysis.mapping_pairs += 1;
+                    analysis.writable_code_in_kibibyte += overlap / 1024;
+                    // the resident pages of the overlap are already counted by the executable view
+                    let double_counted = view.resident_set_size_in_kibibyte * overlap / (view.end - view.start);
+                    analysis.unique_resident_set_size_in_kibibyte -= double_counted;
+                }
+            }
+        }
+
+        analysis.write_xor_execute = if analysis.mapping_count > 0 {
+            WriteXorExecute::Enabled
+        } else if analysis.read_write_execute_anonymous_count > 0 {
+            WriteXorExecute::Disabled
+        } else {
+            WriteXorExecute::Unknown
+        };
+        analysis
+    }
+
+    /// Memory that is counted more than once when the views are summed up
+    pub fn double_counted_in_kibibyte(&self) -> u64 {
+        (self.code_views_in_kibibyte + self.writable_views_in_kibibyte + self.reserved_views_in_kibibyte).saturating_sub(self.unique_in_kibibyte)
+    }
+}
+
+// size of the union of all views in bytes
+fn merged_size(views: &mut [View]) -> u64 {
+    views.sort_by_key(|view| view.start);
+    let mut size = 0;
+    let mut current: Option<(u64, u64)> = None;
+    for view in views.iter() {
+        current = match current {
+            Some((start, end)) if view.start <= end => Some((start, end.max(view.end))),
+            Some((start, end)) => {
+                size += end - start;
+                Some((view.start, view.end))
+            }
+            None => Some((view.start, view.end)),
+        };
+    }
+    size + current.map(|(start, end)| end - start).unwrap_or(0)
+}
+
+impl Display for JitCodeAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "W^X: {}", self.write_xor_execute)?;
+        match self.write_xor_execute {
+            WriteXorExecute::Enabled => {
+                writeln!(f, "Doublemapper Views: {} (Code: {} KiB, Writable: {} KiB, Reserved: {} KiB)", self.mapping_count, self.code_views_in_kibibyte, self.writable_views_in_kibibyte, self.reserved_views_in_kibibyte)?;
+                writeln!(f, "Doublemapper Memory (each file offset counted once): {} KiB (double counted by the views: {} KiB)", self.unique_in_kibibyte, self.double_counted_in_kibibyte())?;
+                writeln!(f, "Executable Memory (each file offset counted once): {} KiB", self.executable_in_kibibyte)?;
+                writeln!(f, "Resident (RSS): {} KiB (of the views: {} KiB)", self.unique_resident_set_size_in_kibibyte, self.resident_set_size_in_kibibyte)?;
+                writeln!(f, "Currently writable Code: {} KiB in {} Mapping Pairs", self.writable_code_in_kibibyte, self.mapping_pairs)?;
+            }
+            WriteXorExecute::Disabled => {
+                writeln!(f, "Read/Write/Execute anonymous Memory: {} KiB in {} Memory Pages", self.read_write_execute_anonymous_in_kibibyte, self.read_write_execute_anonymous_count)?;
+            }
+            WriteXorExecute::Unknown => {}
+        }
+        if self.write_xor_execute == WriteXorExecute::Enabled && self.read_write_execute_anonymous_count > 0 {
+            writeln!(f, "Read/Write/Execute anonymous Memory besides the Doublemapper: {} KiB in {} Memory Pages", self.read_write_execute_anonymous_in_kibibyte, self.read_write_execute_anonymous_count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod jit_code_tests {
+    use super::*;
+    use crate::file_info::FileInfo;
+
+    #[test]
+    fn views_are_paired_by_offset() {
+        let doublemapper = || MappingKind::File(FileInfo::new("/memfd:doublemapper (deleted)"));
+        let pages = PMapVec(vec![
+            PMap { offset: 0x0, inode: 4128, resident_set_size_in_kibibyte: 16, ..PMap::test_page(0x7faf68766000, "r-xs", 16, doublemapper()) },
+            PMap { offset: 0x4000, inode: 4128, resident_set_size_in_kibibyte: 8, ..PMap::test_page(0x7faf6876a000, "rw-s", 8, doublemapper()) },
+            // writable window on the second half of the code
+            PMap { offset: 0x2000, inode: 4128, resident_set_size_in_kibibyte: 8, ..PMap::test_page(0x7faf68800000, "rw-s", 8, doublemapper()) },
+            PMap { offset: 0x6000, inode: 4128, resident_set_size_in_kibibyte: 4, ..PMap::test_page(0x7faf68900000, "---s", 4, doublemapper()) },
+        ]);
+
+        let analysis = JitCodeAnalysis::analyze(&pages);
+        assert_eq!(analysis.write_xor_execute, WriteXorExecute::Enabled);
+        assert_eq!(analysis.mapping_count, 4);
+        assert_eq!(analysis.code_views_in_kibibyte, 16);
+        assert_eq!(analysis.writable_views_in_kibibyte, 16);
+        assert_eq!(analysis.unique_in_kibibyte, 28);
+        assert_eq!(analysis.executable_in_kibibyte, 16);
+        assert_eq!(analysis.double_counted_in_kibibyte(), 8);
+        assert_eq!(analysis.writable_code_in_kibibyte, 8);
+        assert_eq!(analysis.mapping_pairs, 1);
+        assert_eq!(analysis.unique_resident_set_size_in_kibibyte, 28);
+    }
+
+    #[test]
+    fn write_xor_execute_disabled() {
+        let pages = PMapVec(vec![PMap::test_page(0, "rwxp", 64, MappingKind::AnonymousPrivate(None))]);
+
+        let analysis = JitCodeAnalysis::analyze(&pages);
+        assert_eq!(analysis.write_xor_execute, WriteXorExecute::Disabled);
+        assert_eq!(analysis.read_write_execute_anonymous_in_kibibyte, 64);
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt::Display;
+
+use crate::dotnet_regions::{ClassifiedRegionVec, DotnetRegionKind};
+use crate::elf_image::ElfImage;
+use crate::file_info::FileInfo;
+use crate::images::{ImageSegment, LoadedImage, LoadedImageVec, SegmentKind};
+use crate::pe_image::PeImage;
+use crate::perf_map::{PerfMap, PerfMapEntry};
+use crate::pmap::*;
+
+/// Everything known about an address, e.g. of a crash report
+pub struct AddressLookup<'a> {
+    pub address: u64,
+    pub page: Option<&'a PMap>,
+    // classification of anonymous memory
+    pub region_kind: Option<DotnetRegionKind>,
+    pub image: Option<(&'a LoadedImage, &'a ImageSegment)>,
+    // offset of the address in the image file (None for the anonymous segments)
+    pub file_offset: Option<u64>,
+    // ELF or PE section, only known if the image file was found
+    pub section: Option<String>,
+    // nearest ELF symbol and the distance to it
+    pub symbol: Option<(String, u64)>,
+    pub method: Option<&'a PerfMapEntry>,
+}
+
+/// Files, which are needed to symbolize an address
+pub struct LookupContext<'a> {
+    pub memory_pages: &'a PMapIndex<'a>,
+    pub classified_regions: &'a ClassifiedRegionVec,
+    pub images: &'a LoadedImageVec,
+    pub perf_map: Option<&'a PerfMap>,
+    pub sysroot: Option<&'a str>,
+    pub application_folder: Option<&'a str>,
+}
+
+impl<'a> AddressLookup<'a> {
+    pub fn lookup(address: u64, context: &LookupContext<'a>) -> Self {
+        let page = context.memory_pages.find(address);
+        let mut lookup = AddressLookup {
+            address,
+            page,
+            region_kind: page.and_then(|page| context.classified_regions.find(page.address)).map(|region| region.kind),
+            image: context.images.find(address),
+            file_offset: None,
+            section: None,
+            symbol: None,
+            method: context.perf_map.and_then(|perf_map| perf_map.find(address)),
+        };
+
+        let Some((image, segment)) = lookup.image else {
+            return lookup;
+        };
+        if !matches!(segment.kind, SegmentKind::Bss | SegmentKind::Reserved) {
+            lookup.file_offset = Some(segment.offset + address - segment.address);
+        }
+        let Some(bytes) = FileInfo::new(&image.path)
+            .resolve(context.sysroot, context.application_folder)
+            .and_then(|file_info| file_info.read().ok()) else {
+            return lookup;
+        };
+        if let Ok(elf_image) = ElfImage::parse(&bytes) {
+            // shared libraries are linked at address 0 and loaded anywhere
+            let Some(load_bias) = elf_image
+                .virtual_address_of(image.segments[0].offset)
+                .map(|virtual_address| image.start_address().wrapping_sub(virtual_address)) else {
+                return lookup;
+            };
+            let virtual_address = address.wrapping_sub(load_bias);
+            lookup.section = elf_image.section_at_virtual_address(virtual_address).map(|name| name.to_string());
+            lookup.symbol = elf_image.symbol_at(virtual_address).map(|(symbol, offset)| (symbol.name.clone(), offset));
+        } else if let Ok(pe_image) = PeImage::parse(&bytes) {
+            lookup.section = lookup.file_offset.and_then(|offset| pe_image.section_at(offset)).map(|name| name.to_string());
+        }
+        lookup
+    }
+}
+
+impl Display for AddressLookup<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Address: 0x{:x}", self.address)?;
+        let Some(page) = self.page else {
+            return writeln!(f, "  Not part of any memory page");
+        };
+        writeln!(
+            f,
+            "  Memory Page: 0x{:x} - 0x{:x} ({} KiB, {}, Offset: 0x{:x}) {}",
+            page.address,
+            page.end_address(),
+            page.size_in_kibibyte,
+            page.permissions.my_display(),
+            page.offset,
+            page.mapping_kind)?;
+        if let Some(region_kind) = self.region_kind {
+            writeln!(f, "  Region: {}", region_kind)?;
+        }
+        if let Some((image, segment)) = self.image {
+            write!(f, "  Image: {} (Segment: {}", image.path, segment.kind)?;
+            if let Some(file_offset) = self.file_offset {
+                write!(f, ", File Offset: 0x{:x}", file_offset)?;
+            }
+            if let Some(section) = &self.section {
+                write!(f, ", Section: {}", section)?;
+            }
+            writeln!(f, ")")?;
+        }
+        if let Some((name, offset)) = &self.symbol {
+            writeln!(f, "  Symbol: {}+0x{:x}", name, offset)?;
+        }
+        if let Some(method) = self.method {
+            writeln!(f, "  JIT'd Method: {}+0x{:x}", method.name, self.address - method.address)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod lookup_tests {
+    use super::*;
+
+    #[test]
+    fn lookup_code_of_image_and_jit() {
+        let code = PMap {
+            offset: 0x1000,
+            device_major: 8,
+            device_minor: 1,
+            inode: 916305,
+            ..PMap::test_page(0x7faf63f60000, "r-xp", 336, MappingKind::File(FileInfo::new("/does/not/exist/System.Linq.dll")))
+        };
+        let jit = PMap::test_page(0x7f6e842c5000, "rwxp", 64, MappingKind::AnonymousPrivate(None));
+        let memory_pages = PMapVec(vec![jit, code]);
+        let images = LoadedImageVec::from_memory_pages(&memory_pages);
+        let perf_map = PerfMap(vec!["7F6E842C5000 1A0 void [System.Private.CoreLib] System.Threading.Thread::Run()".parse().unwrap()]);
+        let context = LookupContext {
+            memory_pages: &memory_pages.index(),
+            classified_regions: &ClassifiedRegionVec(vec![]),
+            images: &images,
+            perf_map: Some(&perf_map),
+            sysroot: None,
+            application_folder: None,
+        };
+
+        let lookup = AddressLookup::lookup(0x7faf63f60123, &context);
+        assert_eq!(lookup.image.unwrap().0.name(), "System.Linq.dll");
+        assert_eq!(lookup.file_offset, Some(0x1123));
+        assert!(lookup.method.is_none());
+
+        let lookup = AddressLookup::lookup(0x7f6e842c5010, &context);
+        assert!(lookup.image.is_none());
+        assert_eq!(lookup.method.unwrap().size_in_byte, 0x1a0);
+        assert!(lookup.to_string().contains("System.Threading.Thread::Run()+0x10"));
+
+        assert!(AddressLookup::lookup(0x1000, &context).page.is_none());
+    }
+}
@@ -1,22 +1,68 @@
 use std::vec;
 use std::fmt::Write;
 
-use clap::Parser;
-use pmap_analyzer::PMapCategory;
+use address_space::AddressSpaceAnalysis;
+use clap::{CommandFactory, Parser, Subcommand};
+use dotnet_regions::{ClassifiedRegionVec, GcReservation};
+use cgroup::{CgroupMemory, MemoryLimitAnalysis};
+use deleted_files::DeletedFileVec;
+use duplicate_images::DuplicateImageVec;
+use elf_image::NativeLibraryVec;
+use gc_regions::GcRegionsAnalysis;
+use huge_pages::{HugePagesAnalysis, TransparentHugePageSettings};
+use images::LoadedImageVec;
+use jit_code::JitCodeAnalysis;
+use malloc_arenas::MallocArenaAnalysis;
+use map_count::MapCountAnalysis;
+use multi_process::MultiProcessAnalysis;
+use lookup::{AddressLookup, LookupContext};
+use security_audit::{AuditFormat, SecurityAudit, Severity};
+use reclaimable::ReclaimableAnalysis;
+use snapshot_diff::SnapshotDiff;
+use swap::SwapAnalysis;
+use time_series::TimeSeries;
+use memory_regions::{parse_address, MemoryRegionVec, RegionBreakdown};
+use pe_image::ManagedAssemblyVec;
+use process_scan::{DotnetProcessMemory, DotnetProcessTree, ProcessOverview};
+use perf_map::{JitCodeAttribution, JitCodeGrouping, JitCodeGrowth, PerfInfo, PerfMap};
+use pmap_analyzer::{CategorySortKey, PMapCategory};
 
 use crate::pmap::*;
 use crate::file_info::*;
 
 mod pmap;
 mod pmap_analyzer;
+mod dotnet_regions;
+mod gc_regions;
+mod address_space;
+mod map_count;
+mod huge_pages;
+mod swap;
+mod reclaimable;
+mod cgroup;
+mod malloc_arenas;
+mod jit_code;
+mod perf_map;
+mod images;
+mod pe_image;
+mod elf_image;
+mod duplicate_images;
+mod deleted_files;
+mod lookup;
+mod memory_regions;
+mod snapshot_diff;
+mod security_audit;
+mod time_series;
+mod multi_process;
+mod process_scan;
 pub mod file_info;
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
-    /// Path to the file containing the output of the `pmap -XX -p <PID>` command
-    #[clap(short, long)]
-    pmap_output: String,
+    /// Path to the file containing the output of the `pmap -XX -p <PID>` command (or a folder of them for `trend`, the first process for `aggregate`, not needed for `scan`)
+    #[clap(short, long, required = true)]
+    pmap_output: Option<String>,
 
     /// Path to the folder containing the application (executables and libraries)
     #[clap(short, long, default_value = "/app")]
@@ -26,18 +72,289 @@ struct Args {
     #[clap(short, long, default_value = "8192")]
     thread_stack_size: Option<u64>,
 
-    /// Path to csv file, that contains start and end addresses of coalesces memory pages, that should be broken down
+    /// Path to csv file with lines of `start,end[,name]` (hex addresses, `0x` prefix and WinDbg backtick form accepted) of memory regions, that should be broken down
     #[clap(short, long)]
     csv_of_memory_regions: Option<String>,
+
+    /// Path to the root file system of the container, used to read the mapped files on the host
+    #[clap(long)]
+    sysroot: Option<String>,
+
+    /// Include memory pages flagged `DoNotIncludeInCoreDump` (e.g. reserved address ranges) in the breakdown of the memory regions
+    #[clap(long)]
+    include_excluded_from_core_dump: bool,
+
+    /// Column used to order the overview of categories (descending)
+    #[clap(short, long, value_enum, default_value_t = CategorySortKey::Pss)]
+    sort_categories_by: CategorySortKey,
+
+    /// Path to perf map files (`/tmp/perf-<PID>.map`, written with `DOTNET_PerfMapEnabled=1`), several files are treated as snapshots in the given order
+    #[clap(long, num_args = 1..)]
+    perf_map: Vec<String>,
+
+    /// Path to the perfinfo file (`/tmp/perfinfo-<PID>.map`) listing the loaded images
+    #[clap(long)]
+    perfinfo: Option<String>,
+
+    /// Grouping of the JIT'd methods of the perf map
+    #[clap(long, value_enum, default_value_t = JitCodeGrouping::Assembly)]
+    jit_code_grouping: JitCodeGrouping,
+
+    /// Number of the biggest JIT'd methods to show
+    #[clap(long, default_value = "10")]
+    top_methods: usize,
+
+    /// Virtual memory limit of the process in KiB (`ulimit -v`), used to check whether big reservations still fit into the address space
+    #[clap(long)]
+    virtual_memory_limit: Option<u64>,
+
+    /// Maximum number of mappings of a process (`vm.max_map_count`), read from `/proc/sys/vm/max_map_count` if not given
+    #[clap(long)]
+    max_map_count: Option<u64>,
+
+    /// Percentage of `vm.max_map_count` from which on a warning is shown
+    #[clap(long, default_value = "80")]
+    max_map_count_warning: u64,
+
+    /// Folder with the transparent huge page settings of the system (or a file with the content of its `enabled`)
+    #[clap(long, default_value = "/sys/kernel/mm/transparent_hugepage")]
+    thp_settings: String,
+
+    /// Read throughput of the swap device in MiB/s, used to estimate how long a full GC stalls on swapped out GC heap memory
+    #[clap(long, default_value = "100")]
+    swap_read_throughput: u64,
+
+    /// Memory limit in MiB (e.g. of the container), wins over the limit of `--cgroup`
+    #[clap(long)]
+    memory_limit: Option<u64>,
+
+    /// Folder with the memory files of the cgroup of the process (`/sys/fs/cgroup` in the container or a copy of it), v1 and v2 are supported
+    #[clap(long)]
+    cgroup: Option<String>,
+
+    /// Value of `MALLOC_ARENA_MAX` used to estimate the savings of limiting the glibc malloc arenas
+    #[clap(long, default_value = "2")]
+    malloc_arena_max: u64,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Resolves addresses (e.g. of a crash report) to memory page, image, section, symbol and JIT'd method
+    Lookup {
+        /// Addresses in hex, with or without `0x`, the WinDbg form `00007faf`63f60000` is accepted too
+        #[clap(required = true)]
+        addresses: Vec<String>,
+    },
+    /// Compares the pmap output (before) with a later one of the same process (after) to show what grew
+    Diff {
+        /// Path to the file containing the later output of the `pmap -XX -p <PID>` command
+        after: String,
+
+        /// Number of the biggest changes of memory pages to show
+        #[clap(long, default_value = "20")]
+        top: usize,
+    },
+    /// Audits the permissions of the memory pages (writable and executable memory, code from writable locations, stacks without guard page, ...)
+    Audit {
+        /// Output format, `json` for pipelines
+        #[clap(long, value_enum, default_value_t = AuditFormat::Text)]
+        format: AuditFormat,
+
+        /// Exit with code 1 if there is a finding of this or a higher severity
+        #[clap(long, value_enum)]
+        fail_on: Option<Severity>,
+    },
+    /// Builds time series of the categories over many snapshots of the same process (the pmap output included) to find leak suspects
+    Trend {
+        /// Paths to further files or folders with outputs of the `pmap -XX -p <PID>` command, ordered by the time in the file name (`YYYYMMDD_hhmmss`, `YYYY-MM-DD_hh-mm-ss` or unix time) or else by the modification time
+        snapshots: Vec<String>,
+
+        /// Memory limit in MiB (e.g. of the container), the time until the RSS reaches it is projected (defaults to the global `--memory-limit`)
+        #[clap(long)]
+        memory_limit: Option<u64>,
+    },
+    /// Sums up several processes (e.g. of a pod, the pmap output included) with their unique memory and the images they share
+    Aggregate {
+        /// Paths to the files containing the outputs of the `pmap -XX -p <PID>` command of the other processes
+        #[clap(required = true)]
+        pmap_outputs: Vec<String>,
+    },
+    /// Finds the running .NET processes (`dotnet` and apphost executables with the runtime mapped) and shows the categories of each, needs `pmap`
+    Scan {
+        /// Folder with the process folders, for a copy of `/proc` the memory pages are read from `<PID>/smaps` instead of running `pmap`
+        #[clap(long, default_value = process_scan::LIVE_PROC_FOLDER)]
+        proc: String,
+    },
 }
 
 fn main() {
     let args = Args::parse();
-    let pmap_output = FileInfo::new(args.pmap_output);
+    if let Some(Command::Scan { proc }) = &args.command {
+        scan_processes(&args, proc);
+        return;
+    }
+    let Some(pmap_output) = args.pmap_output.clone() else {
+        Args::command().error(clap::error::ErrorKind::MissingRequiredArgument, "--pmap-output is required (except for scan)").exit();
+    };
+    if let Some(Command::Trend { snapshots, memory_limit }) = &args.command {
+        let paths = [vec![pmap_output.clone()], snapshots.clone()].concat();
+        let snapshots = match time_series::collect_snapshots(&paths) {
+            Ok(snapshots) => snapshots,
+            Err(error) => {
+                eprintln!("Could not read the snapshots: {}", error);
+                std::process::exit(1);
+            }
+        };
+        let snapshots = snapshots
+            .into_iter()
+            .filter_map(|(path, timestamp)| {
+                let memory_pages = match PMap::parse_pmap_output(&path) {
+                    Ok(memory_pages) => memory_pages,
+                    Err(error) => {
+                        eprintln!("Skipping the snapshot {}: {}", path, error);
+                        return None;
+                    }
+                };
+                let classified_regions = ClassifiedRegionVec::classify(&memory_pages, args.thread_stack_size.unwrap());
+                let categories = get_categories_from_memory_pages(memory_pages, args.application_folder.clone(), &classified_regions, args.sort_categories_by);
+                Some((path, timestamp, categories))
+            })
+            .collect();
+        println!("Time Series:");
+        println!("{}", TimeSeries::analyze(snapshots, memory_limit.or(args.memory_limit).map(|limit| limit * 1024)));
+        return;
+    }
+    if let Some(Command::Aggregate { pmap_outputs }) = &args.command {
+        let processes = [vec![pmap_output.clone()], pmap_outputs.clone()]
+            .concat()
+            .into_iter()
+            .map(|path| {
+                let memory_pages = get_memory_pages(&FileInfo::new(&path));
+                let classified_regions = ClassifiedRegionVec::classify(&memory_pages, args.thread_stack_size.unwrap());
+                let categories = get_categories_from_memory_pages(memory_pages, args.application_folder.clone(), &classified_regions, args.sort_categories_by);
+                (multi_process::process_name(&path), categories)
+            })
+            .collect();
+        println!("{}", MultiProcessAnalysis::analyze(processes));
+        return;
+    }
+    let pmap_output = FileInfo::new(pmap_output);
     let memory_pages = get_memory_pages(&pmap_output);
-    let categories = get_categories_from_memory_pages(memory_pages.clone(), args.application_folder);
+    let classified_regions = ClassifiedRegionVec::classify(&memory_pages, args.thread_stack_size.unwrap());
+    let memory_page_index = memory_pages.index();
+    let images = LoadedImageVec::from_memory_pages(&memory_pages);
+    let perf_maps = args.perf_map
+        .iter()
+        .filter_map(|path| match PerfMap::parse_perf_map(path) {
+            Ok(perf_map) => Some((path.clone(), perf_map)),
+            Err(error) => {
+                eprintln!("Could not read the perf map {}: {}", path, error);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(Command::Lookup { addresses }) = &args.command {
+        let context = LookupContext {
+            memory_pages: &memory_page_index,
+            classified_regions: &classified_regions,
+            images: &images,
+            perf_map: perf_maps.last().map(|(_, perf_map)| perf_map),
+            sysroot: args.sysroot.as_deref(),
+            application_folder: args.application_folder.as_deref(),
+        };
+        for address in addresses {
+            match parse_address(address) {
+                Ok(address) => println!("{}", AddressLookup::lookup(address, &context)),
+                Err(error) => eprintln!("{}", error),
+            }
+        }
+        return;
+    }
+    if let Some(Command::Audit { format, fail_on }) = &args.command {
+        let audit = SecurityAudit::audit(&memory_page_index, &classified_regions, args.thread_stack_size.unwrap(), *format);
+        print!("{}", audit);
+        if fail_on.is_some_and(|fail_on| audit.max_severity().is_some_and(|severity| severity >= fail_on)) {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::Diff { after, top }) = &args.command {
+        let after_pages = get_memory_pages(&FileInfo::new(after));
+        let after_classified_regions = ClassifiedRegionVec::classify(&after_pages, args.thread_stack_size.unwrap());
+        let before_categories = get_categories_from_memory_pages(memory_pages.clone(), args.application_folder.clone(), &classified_regions, args.sort_categories_by);
+        let after_categories = get_categories_from_memory_pages(after_pages.clone(), args.application_folder.clone(), &after_classified_regions, args.sort_categories_by);
+        println!("Snapshot Diff:");
+        println!("{}", SnapshotDiff::compare(&memory_pages, &after_pages, &before_categories, &after_categories, args.sort_categories_by, *top));
+        return;
+    }
+
+    let categories = get_categories_from_memory_pages(memory_pages.clone(), args.application_folder.clone(), &classified_regions, args.sort_categories_by);
     println!("Overview of Categories:");
     println!("{}\n", categories);
+    println!("Classification of Anonymous Memory Pages:");
+    println!("{}\n", classified_regions);
+    match GcRegionsAnalysis::analyze(&memory_pages) {
+        Some(gc_regions) => {
+            println!("GC Regions Reservation:");
+            println!("{}\n", gc_regions);
+        },
+        None => println!("No GC regions reservation found\n"),
+    }
+    println!("Virtual Address Space:");
+    println!("{}", AddressSpaceAnalysis::analyze(&memory_pages, args.virtual_memory_limit));
+    println!("Mapping Count:");
+    println!("{}", MapCountAnalysis::analyze(&categories, args.max_map_count, args.max_map_count_warning));
+    println!("Transparent Huge Pages:");
+    println!("{}", HugePagesAnalysis::analyze(&categories, TransparentHugePageSettings::read(&args.thp_settings)));
+    println!("Swap:");
+    println!("{}", SwapAnalysis::analyze(&categories, &classified_regions, args.swap_read_throughput));
+    println!("Reclaimable Memory:");
+    println!("{}", ReclaimableAnalysis::analyze(&categories));
+    let cgroup = args.cgroup.as_ref().and_then(|path| match CgroupMemory::read(path) {
+        Ok(cgroup) => Some(cgroup),
+        Err(error) => {
+            eprintln!("Could not read the cgroup: {}", error);
+            None
+        }
+    });
+    println!("Memory Limit:");
+    println!("{}", MemoryLimitAnalysis::analyze(&memory_pages, args.memory_limit.map(|limit| limit * 1024), cgroup));
+    println!("Malloc Arenas:");
+    println!("{}", MallocArenaAnalysis::analyze(&memory_pages, &classified_regions, args.malloc_arena_max));
+    println!("JIT Code:");
+    println!("{}", JitCodeAnalysis::analyze(&memory_pages));
+
+    if let Some((_, perf_map)) = perf_maps.last() {
+        let perf_info = args.perfinfo.as_ref().and_then(|path| match PerfInfo::parse_perf_info(path) {
+            Ok(perf_info) => Some(perf_info),
+            Err(error) => {
+                eprintln!("Could not read the perfinfo {}: {}", path, error);
+                None
+            }
+        });
+        println!("JIT Code by Method:");
+        println!("{}", JitCodeAttribution::attribute(&memory_pages, perf_map, perf_info.as_ref(), args.jit_code_grouping, args.top_methods));
+        if perf_maps.len() > 1 {
+            println!("JIT Code Growth:");
+            println!("{}", JitCodeGrowth(perf_maps.iter().map(|(path, perf_map)| (path.clone(), perf_map)).collect()));
+        }
+    }
+    println!("Loaded Images:");
+    println!("{}\n", images);
+    println!("Managed Assemblies:");
+    println!("{}", ManagedAssemblyVec::analyze(&images, args.sysroot.as_deref(), args.application_folder.as_deref()));
+    println!("Native Libraries:");
+    println!("{}", NativeLibraryVec::analyze(&images, args.sysroot.as_deref(), args.application_folder.as_deref()));
+    println!("Duplicate Images:");
+    println!("{}", DuplicateImageVec::find(&images));
+    println!("Deleted Files:");
+    println!("{}", DeletedFileVec::find(&memory_pages));
+    println!("Security Audit:");
+    println!("{}", SecurityAudit::audit(&memory_page_index, &classified_regions, args.thread_stack_size.unwrap(), AuditFormat::Text));
     println!("Overview of Memory Pages which are bigger than 10 MiB:");
     println!("{}\n", memory_pages);
     let potential_threads: usize = memory_pages.0.iter().filter(|page| 
@@ -63,44 +380,30 @@ fn main() {
 
         println!("{:~<258}", "");
 
+        let memory_regions = match MemoryRegionVec::parse(&memory_regions.read_to_string().unwrap()) {
+            Ok(memory_regions) => memory_regions,
+            Err(errors) => {
+                eprintln!("Invalid file with memory regions:\n{}", errors);
+                return;
+            }
+        };
+        let categorize = |pages: PMapVec| get_categories_from_memory_pages(pages, args.application_folder.clone(), &classified_regions, args.sort_categories_by);
         let mut memory_pages_in_regions = vec![];
-
-        memory_regions.read_to_string().unwrap().lines().for_each(
-            |line| {
-                let line = line.trim();
-                if line.is_empty() {
-                    return; // skip empty lines
-                }
-                let memory_region = line.split(',').map(|s| parse_hex(s.trim().to_string())).collect::<Vec<u64>>();
-                if memory_region.len() != 2 {
-                    eprintln!("Invalid line: {}", line);
-                    return;
-                }
-
-                let start = memory_region[0];
-                let end = memory_region[1];
-
-                println!("Memory Pages in the range: 0x{:x} - 0x{:x}", start, end);
-
-                memory_pages.0.iter().filter(|page| page.address >= start && ( page.address + page.size_in_kibibyte * 1024) <= end).for_each(|page| {
-                    if !page.virtual_memory_flags.contains(VirtualMemoryFlags::DoNotIncludeInCoreDump) {
-                        print!("{}", page);
-                        memory_pages_in_regions.push(page.address);
-                    }
-                });
-
-            },
-        );
+        for memory_region in &memory_regions.0 {
+            let breakdown = RegionBreakdown::analyze(memory_region, &memory_page_index, args.include_excluded_from_core_dump, &categorize);
+            println!("{}", breakdown);
+            memory_pages_in_regions.extend(breakdown.pages.iter().map(|page| page.address));
+        }
 
         if memory_pages_in_regions.is_empty() {
             println!("No memory pages found in the given memory regions");
         } else {
             let mut res = String::new(); 
-            write!(&mut res, "{}", '{').unwrap();
+            write!(&mut res, "{{").unwrap();
             for page_addr in memory_pages_in_regions.iter() {
                 write!(&mut res, " 0x{:x},", page_addr).unwrap();
             }
-            write!(&mut res, "{}", " }").unwrap();
+            write!(&mut res, " }}").unwrap();
 
             println!("{}", res);
         }
@@ -108,18 +411,43 @@ fn main() {
 
 }
 
-fn parse_hex(hex_str: String) -> u64 {
-    u64::from_str_radix(hex_str.replace("`", "").as_str(), 16).unwrap_or(0)
+fn scan_processes(args: &Args, proc_folder: &str) {
+    let tree = DotnetProcessTree::scan(proc_folder);
+    let mut overview = Vec::new();
+    let mut summaries = Vec::new();
+    for process in tree.0 {
+        match process_scan::read_memory_pages(proc_folder, process.pid) {
+            Ok(memory_pages) => {
+                let memory = DotnetProcessMemory {
+                    proportional_share_size_in_kibibyte: memory_pages.0.iter().map(|page| page.proportional_share_size_in_kibibyte).sum(),
+                    gc_reservation_in_kibibyte: GcReservation::find(&memory_pages).map(|reservation| reservation.size_in_kibibyte()),
+                    jit_code_in_kibibyte: JitCodeAnalysis::analyze(&memory_pages).executable_in_kibibyte,
+                };
+                let classified_regions = ClassifiedRegionVec::classify(&memory_pages, args.thread_stack_size.unwrap());
+                let categories = get_categories_from_memory_pages(memory_pages, args.application_folder.clone(), &classified_regions, args.sort_categories_by);
+                summaries.push(format!("{} {}:\n{}", process.pid, process.command_line, categories));
+                overview.push((process, Some(memory)));
+            }
+            Err(error) => {
+                eprintln!("Could not read the memory pages of {}: {}", process.pid, error);
+                overview.push((process, None));
+            }
+        }
+    }
+    println!(".NET Processes:");
+    println!("{}", ProcessOverview(overview));
+    for summary in summaries {
+        println!("{}", summary);
+    }
 }
 
 fn get_memory_pages(input: &FileInfo) -> pmap::PMapVec {
-    let memory_pages = pmap::PMap::parse_pmap_output(input.full_name()).expect("Could not parse pmap output");
-    memory_pages
+    pmap::PMap::parse_pmap_output(input.full_name()).expect("Could not parse pmap output")
 }
 
-fn get_categories_from_memory_pages(memory_pages: pmap::PMapVec, application_folder: Option<String>) -> pmap_analyzer::PMapCategoryVec {
+fn get_categories_from_memory_pages(memory_pages: pmap::PMapVec, application_folder: Option<String>, classified_regions: &ClassifiedRegionVec, sort_by: CategorySortKey) -> pmap_analyzer::PMapCategoryVec {
 
-    let category_lookup = | mapping: MappingKind | -> String {
+    let category_lookup = | page: &PMap | -> String {
 
         let file_lookup = |full_name: &str | -> String {
             if full_name.starts_with("/usr/share/dotnet") {
@@ -127,7 +455,7 @@ fn get_categories_from_memory_pages(memory_pages: pmap::PMapVec, application_fol
             } else if full_name.contains("memfd:doublemapper (deleted)") {
                 "JIT Code".to_string()
             } else if let Some(app_folder) = &application_folder {
-                if full_name.starts_with(&app_folder.as_str()) {
+                if full_name.starts_with(app_folder.as_str()) {
                     "Application".to_string()
                 } else {
                     full_name.to_string()
@@ -137,7 +465,7 @@ fn get_categories_from_memory_pages(memory_pages: pmap::PMapVec, application_fol
             }
         };
 
-        match mapping {
+        match &page.mapping_kind {
             MappingKind::File(file_info) => {
                 if ! file_info.full_name().is_empty() {
                     file_lookup(&file_info.full_name())
@@ -146,15 +474,17 @@ fn get_categories_from_memory_pages(memory_pages: pmap::PMapVec, application_fol
                 }
             },
             MappingKind::AnonymousPrivate(file_info) => {
-                if let Some(full_name) = file_info {
-                    file_lookup(&full_name)
+                if let Some(region) = classified_regions.find(page.address) {
+                    region.kind.to_string()
+                } else if let Some(full_name) = file_info {
+                    file_lookup(full_name)
                 } else {
                     "Anonymous".to_string()
                 }
             },
             MappingKind::AnonymousShared(file_info) => {
                  if let Some(full_name) = file_info {
-                        file_lookup(&full_name)
+                        file_lookup(full_name)
                 } else {
                     "Anonymous".to_string()
                 }
@@ -162,8 +492,7 @@ fn get_categories_from_memory_pages(memory_pages: pmap::PMapVec, application_fol
             _ => "".to_string()
         }
     };
-    let categories = PMapCategory::get_categories_from_memory_pages(memory_pages, &category_lookup).expect("Couldn't generate categories from memory pages");
-    categories
+    PMapCategory::get_categories_from_memory_pages(memory_pages, &category_lookup, sort_by).expect("Couldn't generate categories from memory pages")
 }
 
 #[cfg(test)]
@@ -171,10 +500,9 @@ mod tests {
     use enumflags2::make_bitflags;
 
     use super::*;
-    use crate::pmap::*;
-    use std::path::*;
 
     #[test]
+    #[allow(clippy::zero_prefixed_literal)]
     fn test_pmap_output() {
         let pmap_output = FileInfo::new(std::env::current_dir().unwrap().join("demo_data/pmap_demo"));
 
@@ -199,36 +527,43 @@ mod tests {
             PMap {
                 mapping_kind: MappingKind::Heap,
                 size_in_kibibyte: 10,
+                proportional_share_size_in_kibibyte: 5,
                 ..Default::default()
             },
             PMap {
                 mapping_kind: MappingKind::Stack,
                 size_in_kibibyte: 20,
+                proportional_share_size_in_kibibyte: 20,
                 ..Default::default()
             },
             PMap {
                 mapping_kind: MappingKind::VirtualSystemCall,
                 size_in_kibibyte: 30,
+                proportional_share_size_in_kibibyte: 10,
                 ..Default::default()
             },
             PMap {
                 mapping_kind: MappingKind::Heap,
                 size_in_kibibyte: 40,
+                proportional_share_size_in_kibibyte: 40,
                 ..Default::default()
             },
             PMap {
                 mapping_kind: MappingKind::AnonymousPrivate(None),
                 size_in_kibibyte: 10,
+                proportional_share_size_in_kibibyte: 5,
                 ..Default::default()
             }
         ];
 
-        let categories = get_categories_from_memory_pages(PMapVec(memory_pages), None);
+        let categories = get_categories_from_memory_pages(PMapVec(memory_pages), None, &ClassifiedRegionVec(vec![]), CategorySortKey::Pss);
         assert_eq!(categories.0.len(), 4);
         assert_eq!(categories.0[0].name, "[heap]");
         assert_eq!(categories.0[1].name, "[stack]");
         assert_eq!(categories.0[2].name, "[vsyscall]");
         assert_eq!(categories.0[0].total_size_in_kibibyte, 50);
         assert_eq!(categories.0[0].pages.len(), 2);
+        // sorted by PSS, by size the [vsyscall] would come before the [stack]
+        assert_eq!(categories.0[0].proportional_share_size_in_kibibyte, 45);
     }
 }
\ No newline at end of file
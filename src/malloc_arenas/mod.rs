@@ -0,0 +1,252 @@
+use std::fmt::Display;
+
+use crate::dotnet_regions::{ClassifiedRegionVec, Confidence, DotnetRegionKind};
+use crate::pmap::*;
+
+// glibc reserves every heap of a thread arena as HEAP_MAX_SIZE (64 MiB on 64 bit) aligned to its size
+const MALLOC_ARENA_SIZE_IN_KIBIBYTE: u64 = 64 * 1024;
+
+/// Heap of a glibc thread arena, the committed head and the reserved tail of its 64 MiB reservation
+#[derive(Debug, PartialEq, Clone)]
+pub struct MallocArena {
+    pub address: u64,
+    pub committed_in_kibibyte: u64,
+    pub reserved_in_kibibyte: u64,
+    pub resident_set_size_in_kibibyte: u64,
+    pub confidence: Confidence,
+}
+
+/// Native allocators that replace glibc malloc (preloaded or linked) and don't know `MALLOC_ARENA_MAX`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Allocator {
+    Jemalloc,
+    Mimalloc,
+}
+
+impl Allocator {
+    // part of the library file name
+    fn library(&self) -> &'static str {
+        match self {
+            Allocator::Jemalloc => "libjemalloc",
+            Allocator::Mimalloc => "libmimalloc",
+        }
+    }
+
+    // jemalloc allocates chunks (extents) aligned to 2 MiB, mimalloc segments aligned to 4 MiB (32 MiB since v2)
+    fn alignment_in_kibibyte(&self) -> u64 {
+        match self {
+            Allocator::Jemalloc => 2 * 1024,
+            Allocator::Mimalloc => 4 * 1024,
+        }
+    }
+
+    fn advice(&self) -> &'static str {
+        match self {
+            Allocator::Jemalloc => "the number of arenas is set with MALLOC_CONF=narenas:<N>, dirty_decay_ms returns unused pages sooner",
+            Allocator::Mimalloc => "freed memory is returned after MIMALLOC_PURGE_DELAY milliseconds (MIMALLOC_RESET_DELAY before v2.1)",
+        }
+    }
+}
+
+impl Display for Allocator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Allocator::Jemalloc => "jemalloc".fmt(f),
+            Allocator::Mimalloc => "mimalloc".fmt(f),
+        }
+    }
+}
+
+/// Anonymous memory pages with the layout of a mapped allocator library
+#[derive(Debug, PartialEq, Clone)]
+pub struct AllocatorUsage {
+    pub allocator: Allocator,
+    pub library: String,
+    pub memory_pages: usize,
+    pub committed_in_kibibyte: u64,
+    pub resident_set_size_in_kibibyte: u64,
+}
+
+/// glibc malloc arenas and the memory `MALLOC_ARENA_MAX` would save
+pub struct MallocArenaAnalysis {
+    // brk heap (`[heap]`) of the main arena, size and RSS
+    pub main_arena: Option<(u64, u64)>,
+    // heaps of the thread arenas ordered by RSS
+    pub arenas: Vec<MallocArena>,
+    pub malloc_arena_max: u64,
+    pub other_allocators: Vec<AllocatorUsage>,
+}
+
+impl MallocArenaAnalysis {
+    pub fn analyze(memory_pages: &PMapVec, classified_regions: &ClassifiedRegionVec, malloc_arena_max: u64) -> Self {
+        let mut main_arena: Option<(u64, u64)> = None;
+        let mut arenas: Vec<MallocArena> = Vec::new();
+        for page in &memory_pages.0 {
+            if page.mapping_kind == MappingKind::Heap {
+                let (size, resident_set_size) = main_arena.unwrap_or_default();
+                main_arena = Some((size + page.size_in_kibibyte, resident_set_size + page.resident_set_size_in_kibibyte));
+                continue;
+            }
+            let Some(region) = classified_regions.find(page.address).filter(|region| region.kind == DotnetRegionKind::MallocArena) else {
+                continue;
+            };
+            // the reserved tail belongs to the heap whose head starts at the aligned address below it
+            let address = page.address - page.address % (MALLOC_ARENA_SIZE_IN_KIBIBYTE * 1024);
+            let arena = match arenas.iter_mut().find(|arena| arena.address == address) {
+                Some(arena) => arena,
+                None => {
+                    arenas.push(MallocArena {
+                        address,
+                        committed_in_kibibyte: 0,
+                        reserved_in_kibibyte: 0,
+                        resident_set_size_in_kibibyte: 0,
+                        confidence: region.confidence,
+                    });
+                    arenas.last_mut().unwrap()
+                }
+            };
+            if page.permissions.intersects(Permissions::Read | Permissions::Write) {
+                arena.committed_in_kibibyte += page.size_in_kibibyte;
+            } else {
+                arena.reserved_in_kibibyte += page.size_in_kibibyte;
+            }
+            arena.resident_set_size_in_kibibyte += page.resident_set_size_in_kibibyte;
+            arena.confidence = arena.confidence.min(region.confidence);
+        }
+        arenas.sort_by_key(|arena| (std::cmp::Reverse(arena.resident_set_size_in_kibibyte), arena.address));
+
+        let other_allocators = [Allocator::Jemalloc, Allocator::Mimalloc]
+            .into_iter()
+            .filter_map(|allocator| {
+                let library = memory_pages.0.iter().find_map(|page| match &page.mapping_kind {
+                    MappingKind::File(file_info) if file_info.name().starts_with(allocator.library()) => Some(file_info.name()),
+                    _ => None,
+                })?;
+                let alignment = allocator.alignment_in_kibibyte() * 1024;
+                let pages = memory_pages.0
+                    .iter()
+                    .filter(|page| page.mapping_kind == MappingKind::AnonymousPrivate(None) && classified_regions.find(page.address).is_none())
+                    .filter(|page| page.permissions.contains(Permissions::Write) && page.address.is_multiple_of(alignment))
+                    .collect::<Vec<_>>();
+                Some(AllocatorUsage {
+                    allocator,
+                    library,
+                    memory_pages: pages.len(),
+                    committed_in_kibibyte: pages.iter().map(|page| page.size_in_kibibyte).sum(),
+                    resident_set_size_in_kibibyte: pages.iter().map(|page| page.resident_set_size_in_kibibyte).sum(),
+                })
+            })
+            .collect();
+
+        MallocArenaAnalysis { main_arena, arenas, malloc_arena_max, other_allocators }
+    }
+
+    /// Thread arenas beyond `MALLOC_ARENA_MAX`, the main arena counts against the limit too
+    pub fn excess_arenas(&self) -> &[MallocArena] {
+        let kept = (self.malloc_arena_max.max(1) - 1) as usize;
+        self.arenas.get(kept..).unwrap_or_default()
+    }
+
+    /// Upper bound of the RSS saved with `MALLOC_ARENA_MAX`: the smallest arenas go away,
+    /// but their live allocations move to the remaining arenas
+    pub fn savings_in_kibibyte(&self) -> u64 {
+        self.excess_arenas().iter().map(|arena| arena.resident_set_size_in_kibibyte).sum()
+    }
+}
+
+const MALLOC_ARENA_TABLE_SEPARATOR: &str = "|-----------------|----------------|-----------------|----------------|------------|------------|\n";
+
+impl Display for MallocArenaAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.main_arena.is_none() && self.arenas.is_empty() && self.other_allocators.is_empty() {
+            return writeln!(f, "No malloc arenas found");
+        }
+        if self.main_arena.is_some() || !self.arenas.is_empty() {
+            writeln!(
+                f,
+                "glibc Arenas: {} ({} thread arena heaps and the main arena), {} KiB committed, {} KiB RSS",
+                self.arenas.len() + 1,
+                self.arenas.len(),
+                self.main_arena.map(|(size, _)| size).unwrap_or(0) + self.arenas.iter().map(|arena| arena.committed_in_kibibyte).sum::<u64>(),
+                self.main_arena.map(|(_, resident_set_size)| resident_set_size).unwrap_or(0) + self.arenas.iter().map(|arena| arena.resident_set_size_in_kibibyte).sum::<u64>())?;
+            MALLOC_ARENA_TABLE_SEPARATOR.fmt(f)?;
+            format!("| {:15} | {:14} | {:15} | {:14} | {:10} | {:10} |\n", "Arena", "Address", "Committed [KiB]", "Reserved [KiB]", "RSS [KiB]", "Confidence").fmt(f)?;
+            MALLOC_ARENA_TABLE_SEPARATOR.fmt(f)?;
+            if let Some((size, resident_set_size)) = self.main_arena {
+                format!("| {:15} | {:14} | {:15} | {:14} | {:10} | {:10} |\n", "main ([heap])", "", size, 0, resident_set_size, "High").fmt(f)?;
+            }
+            for (index, arena) in self.arenas.iter().enumerate() {
+                format!(
+                    "| {:15} | {:14x} | {:15} | {:14} | {:10} | {:10} |\n",
+                    format!("#{}", index + 1),
+                    arena.address,
+                    arena.committed_in_kibibyte,
+                    arena.reserved_in_kibibyte,
+                    arena.resident_set_size_in_kibibyte,
+                    arena.confidence).fmt(f)?;
+            }
+            MALLOC_ARENA_TABLE_SEPARATOR.fmt(f)?;
+            if self.excess_arenas().is_empty() {
+                writeln!(f, "The arenas fit into MALLOC_ARENA_MAX={}, glibc creates up to 8 arenas per CPU core by default", self.malloc_arena_max)?;
+            } else {
+                writeln!(
+                    f,
+                    "MALLOC_ARENA_MAX={} saves up to {} KiB RSS of the {} smallest arena heaps, their live allocations move to the remaining arenas",
+                    self.malloc_arena_max,
+                    self.savings_in_kibibyte(),
+                    self.excess_arenas().len())?;
+                writeln!(f, "A thread arena grows by further 64 MiB heaps, so the number of arenas may be smaller than the number of heaps")?;
+            }
+        }
+        for usage in &self.other_allocators {
+            writeln!(
+                f,
+                "{} is mapped ({}): {} KiB committed and {} KiB RSS in {} anonymous memory pages aligned like its chunks",
+                usage.allocator,
+                usage.library,
+                usage.committed_in_kibibyte,
+                usage.resident_set_size_in_kibibyte,
+                usage.memory_pages)?;
+            writeln!(f, "MALLOC_ARENA_MAX has no effect on {}, {}", usage.allocator, usage.allocator.advice())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod malloc_arenas_tests {
+    use super::*;
+    use crate::file_info::FileInfo;
+    use enumflags2::BitFlags;
+
+    #[test]
+    fn count_arenas_and_estimate_savings() {
+        let flags = <BitFlags<VirtualMemoryFlags> as MyFromStr>::from_str("rd wr mr mw me nr sd").unwrap();
+        let anonymous = MappingKind::AnonymousPrivate(None);
+        let named = MappingKind::AnonymousPrivate(Some(" glibc: malloc arena".to_string()));
+        let pages = PMapVec(vec![
+            PMap { resident_set_size_in_kibibyte: 1800, virtual_memory_flags: flags, ..PMap::test_page(0x55d0c0000000, "rw-p", 1920, MappingKind::Heap) },
+            PMap { resident_set_size_in_kibibyte: 6000, virtual_memory_flags: flags, ..PMap::test_page(0x7f6e44000000, "rw-p", 8192, anonymous.clone()) },
+            PMap { virtual_memory_flags: flags, ..PMap::test_page(0x7f6e44800000, "---p", 57344, anonymous.clone()) },
+            PMap { resident_set_size_in_kibibyte: 100, virtual_memory_flags: flags, ..PMap::test_page(0x7f6e48000000, "rw-p", 132, named.clone()) },
+            PMap { virtual_memory_flags: flags, ..PMap::test_page(0x7f6e48021000, "---p", 65404, named) },
+            PMap { resident_set_size_in_kibibyte: 500, virtual_memory_flags: flags, ..PMap::test_page(0x7f6e4c000000, "rw-p", 1024, anonymous.clone()) },
+            PMap { virtual_memory_flags: flags, ..PMap::test_page(0x7f6e4c100000, "---p", 64512, anonymous.clone()) },
+            PMap { resident_set_size_in_kibibyte: 700, virtual_memory_flags: flags, ..PMap::test_page(0x7f6f00000000, "r-xp", 700, MappingKind::File(FileInfo::new("/usr/lib/libjemalloc.so.2"))) },
+            PMap { resident_set_size_in_kibibyte: 2048, virtual_memory_flags: flags, ..PMap::test_page(0x7f6f10200000, "rw-p", 4096, anonymous) },
+        ]);
+        let classified_regions = ClassifiedRegionVec::classify(&pages, 8192);
+
+        let analysis = MallocArenaAnalysis::analyze(&pages, &classified_regions, 2);
+        assert_eq!(analysis.main_arena, Some((1920, 1800)));
+        let arenas = analysis.arenas.iter().map(|arena| (arena.address, arena.committed_in_kibibyte, arena.reserved_in_kibibyte, arena.resident_set_size_in_kibibyte)).collect::<Vec<_>>();
+        assert_eq!(arenas, vec![(0x7f6e44000000, 8192, 57344, 6000), (0x7f6e4c000000, 1024, 64512, 500), (0x7f6e48000000, 132, 65404, 100)]);
+        // the main arena and the biggest thread arena stay
+        assert_eq!(analysis.excess_arenas().len(), 2);
+        assert_eq!(analysis.savings_in_kibibyte(), 600);
+
+        assert_eq!(analysis.other_allocators.len(), 1);
+        let jemalloc = &analysis.other_allocators[0];
+        assert_eq!((jemalloc.allocator, jemalloc.memory_pages, jemalloc.committed_in_kibibyte), (Allocator::Jemalloc, 1, 4096));
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt::Display;
+
+use crate::pmap_analyzer::PMapCategoryVec;
+
+// default of vm.max_map_count
+const DEFAULT_MAX_MAP_COUNT: u64 = 65530;
+const MAX_MAP_COUNT_PATH: &str = "/proc/sys/vm/max_map_count";
+// mappings up to this size are counted as small (e.g. doublemapper fragments of the JIT'd code)
+const SMALL_MAPPING_IN_KIBIBYTE: u64 = 64;
+// number of categories with the most mappings to show
+const TOP_CATEGORIES: usize = 10;
+
+/// Where the maximum number of mappings was taken from
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MapCountLimitSource {
+    Argument,
+    System,
+    Default,
+}
+
+impl Display for MapCountLimitSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapCountLimitSource::Argument => "--max-map-count".fmt(f),
+            MapCountLimitSource::System => MAX_MAP_COUNT_PATH.fmt(f),
+            MapCountLimitSource::Default => "kernel default".fmt(f),
+        }
+    }
+}
+
+/// Number of mappings of a category
+#[derive(Debug, PartialEq)]
+pub struct CategoryMapCount {
+    pub name: String,
+    pub count: usize,
+    // mappings up to 64 KiB
+    pub small_count: usize,
+    pub size_in_kibibyte: u64,
+}
+
+/// Number of mappings compared to `vm.max_map_count`
+#[derive(Debug, PartialEq)]
+pub struct MapCountAnalysis {
+    pub count: usize,
+    pub limit: u64,
+    pub limit_source: MapCountLimitSource,
+    // percentage of the limit from which on a warning is shown
+    pub warning_threshold_in_percent: u64,
+    // ordered by the number of mappings
+    pub categories: Vec<CategoryMapCount>,
+}
+
+impl MapCountAnalysis {
+    /// Counts the mappings per category, the limit is taken from the argument,
+    /// `/proc/sys/vm/max_map_count` or the kernel default in this order
+    pub fn analyze(categories: &PMapCategoryVec, limit: Option<u64>, warning_threshold_in_percent: u64) -> Self {
+        let (limit, limit_source) = match limit {
+            Some(limit) => (limit, MapCountLimitSource::Argument),
+            None => match std::fs::read_to_string(MAX_MAP_COUNT_PATH).ok().and_then(|value| value.trim().parse().ok()) {
+                Some(limit) => (limit, MapCountLimitSource::System),
+                None => (DEFAULT_MAX_MAP_COUNT, MapCountLimitSource::Default),
+            },
+        };
+        let mut category_counts = categories.0
+            .iter()
+            .map(|category| CategoryMapCount {
+                name: category.name.clone(),
+                count: category.pages.len(),
+                small_count: category.pages.iter().filter(|page| page.size_in_kibibyte <= SMALL_MAPPING_IN_KIBIBYTE).count(),
+                size_in_kibibyte: category.total_size_in_kibibyte,
+            })
+            .collect::<Vec<_>>();
+        category_counts.sort_by_key(|category| std::cmp::Reverse(category.count));
+        MapCountAnalysis {
+            count: category_counts.iter().map(|category| category.count).sum(),
+            limit,
+            limit_source,
+            warning_threshold_in_percent,
+            categories: category_counts,
+        }
+    }
+
+    pub fn headroom(&self) -> i64 {
+        self.limit as i64 - self.count as i64
+    }
+
+    pub fn usage_in_percent(&self) -> f64 {
+        100.0 * self.count as f64 / self.limit as f64
+    }
+
+    pub fn exceeds_warning_threshold(&self) -> bool {
+        self.usage_in_percent() >= self.warning_threshold_in_percent as f64
+    }
+}
+
+const MAP_COUNT_TABLE_SEPARATOR: &str = "|----------------------------------------------------------|------------|------------|-----------------|-----------------|\n";
+
+impl Display for MapCountAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Mappings: {} of {} ({:.1} %, limit from {})", self.count, self.limit, self.usage_in_percent(), self.limit_source)?;
+        writeln!(f, "Headroom: {} mappings", self.headroom())?;
+        if self.exceeds_warning_threshold() {
+            writeln!(
+                f,
+                "Warning: more than {} % of vm.max_map_count is used, mmap fails with ENOMEM when the limit is reached (raise it with `sysctl vm.max_map_count`)",
+                self.warning_threshold_in_percent)?;
+        }
+        writeln!(f)?;
+
+        MAP_COUNT_TABLE_SEPARATOR.fmt(f)?;
+        format!("| {:56} | {:10} | {:10} | {:15} | {:15} |\n", "Category", "#Mappings", "Share [%]", "#Small (<=64K)", "Avg. Size [KiB]").fmt(f)?;
+        MAP_COUNT_TABLE_SEPARATOR.fmt(f)?;
+        for category in self.categories.iter().take(TOP_CATEGORIES) {
+            format!(
+                "| {:56} | {:10} | {:10.1} | {:15} | {:15} |\n",
+                category.name,
+                category.count,
+                100.0 * category.count as f64 / self.count.max(1) as f64,
+                category.small_count,
+                category.size_in_kibibyte / category.count.max(1) as u64).fmt(f)?;
+        }
+        MAP_COUNT_TABLE_SEPARATOR.fmt(f)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod map_count_tests {
+    use super::*;
+    use crate::pmap::*;
+    use crate::pmap_analyzer::{CategorySortKey, PMapCategory};
+
+    #[test]
+    fn count_mappings_per_category() {
+        let page = |mapping_kind: MappingKind, size_in_kibibyte: u64| PMap { size_in_kibibyte, mapping_kind, ..Default::default() };
+        let mut pages = vec![page(MappingKind::Heap, 1024), page(MappingKind::Stack, 132)];
+        pages.extend((0..8).map(|_| page(MappingKind::AnonymousPrivate(None), 4)));
+        let categories = PMapCategory::get_categories_from_memory_pages(PMapVec(pages), &|_| "Anonymous".to_string(), CategorySortKey::Pss).unwrap();
+
+        let analysis = MapCountAnalysis::analyze(&categories, Some(12), 80);
+        assert_eq!((analysis.count, analysis.headroom(), analysis.limit_source), (10, 2, MapCountLimitSource::Argument));
+        assert!(analysis.exceeds_warning_threshold());
+        assert_eq!(analysis.categories[0], CategoryMapCount { name: "Anonymous".to_string(), count: 8, small_count: 8, size_in_kibibyte: 32 });
+
+        assert!(!MapCountAnalysis::analyze(&categories, Some(65530), 80).exceeds_warning_threshold());
+    }
+}
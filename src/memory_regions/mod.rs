@@ -0,0 +1,197 @@
+use std::fmt::Display;
+
+use crate::pmap::*;
+use crate::pmap_analyzer::PMapCategoryVec;
+
+// Sample csv file with memory regions, e.g. taken from the `!address` or `!heap` output of WinDbg:
+// # start, end, name (optional)
+// 0x7f6f4c000000, 0x7f6f4c100000, GC gen0
+// 00007faf`63f50000, 00007faf`64017000
+
+/// Parses a hex address with optional `0x` prefix and WinDbg backtick separator (`00007faf`63f60000`)
+pub fn parse_address(address: &str) -> Result<u64, String> {
+    let digits = address.trim().replace('`', "");
+    let digits = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")).unwrap_or(&digits);
+    u64::from_str_radix(digits, 16).map_err(|_| format!("Can't parse address: {}", address.trim()))
+}
+
+/// Address range [start, end) whose memory pages should be broken down
+#[derive(Debug, PartialEq, Clone)]
+pub struct MemoryRegion {
+    pub name: Option<String>,
+    pub start: u64,
+    pub end: u64,
+}
+
+pub struct MemoryRegionVec(pub Vec<MemoryRegion>);
+
+impl MemoryRegionVec {
+    /// Parses lines of `start,end[,name]`, empty lines and lines starting with `#` are skipped.
+    /// All invalid lines are reported with their line number.
+    pub fn parse(csv: &str) -> Result<Self, String> {
+        let mut regions = Vec::new();
+        let mut errors = Vec::new();
+        for (index, line) in csv.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut columns = line.splitn(3, ',');
+            let start = columns.next().map(parse_address).unwrap_or(Err("Missing start address".to_string()));
+            let end = columns.next().map(parse_address).unwrap_or(Err("Missing end address".to_string()));
+            let name = columns.next().map(|name| name.trim().to_string()).filter(|name| !name.is_empty());
+            match (start, end) {
+                (Ok(start), Ok(end)) if start < end => regions.push(MemoryRegion { name, start, end }),
+                (Ok(_), Ok(_)) => errors.push(format!("Line {}: start address must be below the end address: {}", index + 1, line)),
+                (Err(error), _) | (_, Err(error)) => errors.push(format!("Line {}: {}", index + 1, error)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(MemoryRegionVec(regions))
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+}
+
+/// Memory pages inside of a region with their totals
+pub struct RegionBreakdown<'a> {
+    pub region: &'a MemoryRegion,
+    // pages completely inside of the region
+    pub pages: Vec<&'a PMap>,
+    // pages flagged DoNotIncludeInCoreDump, that were left out
+    pub excluded_pages: usize,
+    // pages crossing the boundaries of the region, which are not included
+    pub crossing_pages: usize,
+    pub categories: PMapCategoryVec,
+}
+
+impl<'a> RegionBreakdown<'a> {
+    pub fn analyze(
+        region: &'a MemoryRegion,
+        memory_pages: &PMapIndex<'a>,
+        include_excluded_from_core_dump: bool,
+        categorize: &dyn Fn(PMapVec) -> PMapCategoryVec)
+        -> Self {
+        let inside = memory_pages.range(region.start, region.end);
+        let (pages, excluded): (Vec<&PMap>, Vec<&PMap>) = inside
+            .iter()
+            .partition(|page| include_excluded_from_core_dump || !page.virtual_memory_flags.contains(VirtualMemoryFlags::DoNotIncludeInCoreDump));
+        RegionBreakdown {
+            region,
+            categories: categorize(PMapVec(pages.iter().map(|page| (*page).clone()).collect())),
+            pages,
+            excluded_pages: excluded.len(),
+            crossing_pages: memory_pages.overlapping(region.start, region.end).len() - inside.len(),
+        }
+    }
+
+    pub fn size_in_kibibyte(&self) -> u64 {
+        self.pages.iter().map(|page| page.size_in_kibibyte).sum()
+    }
+
+    pub fn resident_set_size_in_kibibyte(&self) -> u64 {
+        self.pages.iter().map(|page| page.resident_set_size_in_kibibyte).sum()
+    }
+
+    pub fn proportional_share_size_in_kibibyte(&self) -> u64 {
+        self.pages.iter().map(|page| page.proportional_share_size_in_kibibyte).sum()
+    }
+}
+
+const REGION_CATEGORY_TABLE_SEPARATOR: &str = "|----------------------------------------------------------|------------|------------|------------|-----------------|\n";
+
+impl Display for RegionBreakdown<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.region.name {
+            Some(name) => writeln!(f, "Memory Pages in the range: 0x{:x} - 0x{:x} ({})", self.region.start, self.region.end, name)?,
+            None => writeln!(f, "Memory Pages in the range: 0x{:x} - 0x{:x}", self.region.start, self.region.end)?,
+        }
+        for page in &self.pages {
+            page.fmt(f)?;
+        }
+        writeln!(
+            f,
+            "Size: {} KiB, RSS: {} KiB, PSS: {} KiB, Memory Pages: {}",
+            self.size_in_kibibyte(),
+            self.resident_set_size_in_kibibyte(),
+            self.proportional_share_size_in_kibibyte(),
+            self.pages.len())?;
+        if self.excluded_pages > 0 {
+            writeln!(f, "{} memory page(s) flagged DoNotIncludeInCoreDump are not included", self.excluded_pages)?;
+        }
+        if self.crossing_pages > 0 {
+            writeln!(f, "{} memory page(s) crossing the boundaries of the range are not included", self.crossing_pages)?;
+        }
+        if self.pages.is_empty() {
+            return Ok(());
+        }
+
+        REGION_CATEGORY_TABLE_SEPARATOR.fmt(f)?;
+        format!("| {:56} | {:10} | {:10} | {:10} | {:15} |\n", "Category", "Size [KiB]", "RSS [KiB]", "PSS [KiB]", "#Memory Pages").fmt(f)?;
+        REGION_CATEGORY_TABLE_SEPARATOR.fmt(f)?;
+        for category in &self.categories.0 {
+            format!(
+                "| {:56} | {:10} | {:10} | {:10} | {:15} |\n",
+                category.name,
+                category.total_size_in_kibibyte,
+                category.resident_set_size_in_kibibyte,
+                category.proportional_share_size_in_kibibyte,
+                category.pages.len()).fmt(f)?;
+        }
+        REGION_CATEGORY_TABLE_SEPARATOR.fmt(f)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod memory_regions_tests {
+    use super::*;
+    use crate::pmap_analyzer::{CategorySortKey, PMapCategory};
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(parse_address("7faf63f60000"), Ok(0x7faf63f60000));
+        assert_eq!(parse_address("0x7faf63f60000"), Ok(0x7faf63f60000));
+        assert_eq!(parse_address("00007faf`63f60000"), Ok(0x7faf63f60000));
+        assert!(parse_address("main+0x10").is_err());
+    }
+
+    #[test]
+    fn parse_regions_with_names_and_errors() {
+        let regions = MemoryRegionVec::parse("# start, end, name\n0x1000,0x2000,GC gen0\n\n00000000`00003000, 4000\n").unwrap();
+        assert_eq!(regions.0, vec![
+            MemoryRegion { name: Some("GC gen0".to_string()), start: 0x1000, end: 0x2000 },
+            MemoryRegion { name: None, start: 0x3000, end: 0x4000 },
+        ]);
+
+        let error = MemoryRegionVec::parse("0x1000,0x2000\nzz,0x2000\n0x3000\n0x4000,0x1000").err().unwrap();
+        assert_eq!(error, "Line 2: Can't parse address: zz\nLine 3: Missing end address\nLine 4: start address must be below the end address: 0x4000,0x1000");
+    }
+
+    #[test]
+    fn breakdown_with_totals() {
+        let page = |address: u64, size_in_kibibyte: u64, excluded: bool| PMap {
+            address,
+            size_in_kibibyte,
+            resident_set_size_in_kibibyte: size_in_kibibyte / 2,
+            proportional_share_size_in_kibibyte: size_in_kibibyte / 4,
+            virtual_memory_flags: if excluded { VirtualMemoryFlags::DoNotIncludeInCoreDump.into() } else { Default::default() },
+            mapping_kind: MappingKind::Heap,
+            ..Default::default()
+        };
+        let memory_pages = PMapVec(vec![page(0x0, 8, false), page(0x2000, 8, false), page(0x4000, 4, true), page(0x5000, 8, false)]);
+        let index = memory_pages.index();
+        let region = MemoryRegion { name: None, start: 0x1000, end: 0x6000 };
+        let categorize = |pages: PMapVec| PMapCategory::get_categories_from_memory_pages(pages, &|_| String::new(), CategorySortKey::Pss).unwrap();
+
+        let breakdown = RegionBreakdown::analyze(&region, &index, false, &categorize);
+        assert_eq!(breakdown.pages.len(), 1);
+        assert_eq!((breakdown.size_in_kibibyte(), breakdown.resident_set_size_in_kibibyte(), breakdown.proportional_share_size_in_kibibyte()), (8, 4, 2));
+        assert_eq!((breakdown.excluded_pages, breakdown.crossing_pages), (1, 2));
+        assert_eq!(breakdown.categories.0[0].name, "[heap]");
+
+        let breakdown = RegionBreakdown::analyze(&region, &index, true, &categorize);
+        assert_eq!(breakdown.size_in_kibibyte(), 12);
+    }
+}
@@ -0,0 +1,278 @@
+use std::fmt::Display;
+
+use crate::file_info::FileInfo;
+use crate::pmap::*;
+use crate::pmap_analyzer::PMapCategoryVec;
+
+/// Name of the process from the first line of the pmap output (`<PID>:   <command line>`), the file name if there is none
+pub fn process_name(path: &str) -> String {
+    let file_info = FileInfo::new(path);
+    let header = file_info
+        .read_to_string()
+        .ok()
+        .and_then(|content| content.lines().next().map(|line| line.to_string()))
+        .and_then(|line| {
+            let (pid, command) = line.split_once(':')?;
+            let pid = pid.trim().parse::<u64>().ok()?;
+            Some(format!("{} {}", pid, short_command_line(command.split_whitespace())))
+        });
+    header.unwrap_or_else(|| file_info.name())
+}
+
+/// Command line with the file names of the paths only, the path of the executable or dll is long and its file name is enough to recognize the process
+pub fn short_command_line<'a>(arguments: impl Iterator<Item = &'a str>) -> String {
+    arguments
+        .map(|argument| argument.rsplit('/').next().unwrap_or(argument))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn unique_set_size(page: &PMap) -> u64 {
+    page.private_clean_in_kibibyte + page.private_dirty_in_kibibyte
+}
+
+/// Memory of one of the processes
+#[derive(Debug, PartialEq)]
+pub struct ProcessSummary {
+    pub name: String,
+    pub resident_set_size_in_kibibyte: u64,
+    pub proportional_share_size_in_kibibyte: u64,
+    // private clean and private dirty, freed when the process exits
+    pub unique_set_size_in_kibibyte: u64,
+}
+
+/// Mapped file shared by several processes
+#[derive(Debug, PartialEq)]
+pub struct SharedImage {
+    pub name: String,
+    device: (u16, u16),
+    inode: u64,
+    pub processes: usize,
+    // sums over all processes
+    pub resident_set_size_in_kibibyte: u64,
+    pub proportional_share_size_in_kibibyte: u64,
+}
+
+impl SharedImage {
+    /// Memory each process would need for its own copy minus the memory the shared pages take
+    pub fn savings_in_kibibyte(&self) -> u64 {
+        self.resident_set_size_in_kibibyte.saturating_sub(self.proportional_share_size_in_kibibyte)
+    }
+}
+
+/// Category summed up over all processes
+#[derive(Debug, PartialEq)]
+pub struct CombinedCategory {
+    pub name: String,
+    pub processes: usize,
+    pub resident_set_size_in_kibibyte: u64,
+    pub proportional_share_size_in_kibibyte: u64,
+    pub unique_set_size_in_kibibyte: u64,
+}
+
+/// Memory of several processes (e.g. of a pod) and what they share
+pub struct MultiProcessAnalysis {
+    pub processes: Vec<ProcessSummary>,
+    // images mapped by at least two processes ordered by the savings
+    pub shared_images: Vec<SharedImage>,
+    // ordered by PSS
+    pub categories: Vec<CombinedCategory>,
+}
+
+impl MultiProcessAnalysis {
+    pub fn analyze(processes: Vec<(String, PMapCategoryVec)>) -> Self {
+        let mut summaries = Vec::new();
+        let mut images: Vec<SharedImage> = Vec::new();
+        let mut categories: Vec<CombinedCategory> = Vec::new();
+        for (name, process_categories) in &processes {
+            let pages = process_categories.0.iter().flat_map(|category| category.pages.iter());
+            summaries.push(ProcessSummary {
+                name: name.clone(),
+                resident_set_size_in_kibibyte: pages.clone().map(|page| page.resident_set_size_in_kibibyte).sum(),
+                proportional_share_size_in_kibibyte: pages.clone().map(|page| page.proportional_share_size_in_kibibyte).sum(),
+                unique_set_size_in_kibibyte: pages.clone().map(unique_set_size).sum(),
+            });
+
+            // the same file is only the same image on the same device
+            let mut process_images: Vec<SharedImage> = Vec::new();
+            for page in pages {
+                let MappingKind::File(file_info) = &page.mapping_kind else {
+                    continue;
+                };
+                if page.inode == 0 {
+                    continue;
+                }
+                let device = (page.device_major, page.device_minor);
+                match process_images.iter_mut().find(|image| image.device == device && image.inode == page.inode) {
+                    Some(image) => {
+                        image.resident_set_size_in_kibibyte += page.resident_set_size_in_kibibyte;
+                        image.proportional_share_size_in_kibibyte += page.proportional_share_size_in_kibibyte;
+                    }
+                    None => process_images.push(SharedImage {
+                        name: file_info.name(),
+                        device,
+                        inode: page.inode,
+                        processes: 1,
+                        resident_set_size_in_kibibyte: page.resident_set_size_in_kibibyte,
+                        proportional_share_size_in_kibibyte: page.proportional_share_size_in_kibibyte,
+                    }),
+                }
+            }
+            for process_image in process_images {
+                match images.iter_mut().find(|image| image.device == process_image.device && image.inode == process_image.inode) {
+                    Some(image) => {
+                        image.processes += 1;
+                        image.resident_set_size_in_kibibyte += process_image.resident_set_size_in_kibibyte;
+                        image.proportional_share_size_in_kibibyte += process_image.proportional_share_size_in_kibibyte;
+                    }
+                    None => images.push(process_image),
+                }
+            }
+
+            for category in &process_categories.0 {
+                let unique_set_size_in_kibibyte = category.private_clean_in_kibibyte + category.private_dirty_in_kibibyte;
+                match categories.iter_mut().find(|combined| combined.name == category.name) {
+                    Some(combined) => {
+                        combined.processes += 1;
+                        combined.resident_set_size_in_kibibyte += category.resident_set_size_in_kibibyte;
+                        combined.proportional_share_size_in_kibibyte += category.proportional_share_size_in_kibibyte;
+                        combined.unique_set_size_in_kibibyte += unique_set_size_in_kibibyte;
+                    }
+                    None => categories.push(CombinedCategory {
+                        name: category.name.clone(),
+                        processes: 1,
+                        resident_set_size_in_kibibyte: category.resident_set_size_in_kibibyte,
+                        proportional_share_size_in_kibibyte: category.proportional_share_size_in_kibibyte,
+                        unique_set_size_in_kibibyte,
+                    }),
+                }
+            }
+        }
+        images.retain(|image| image.processes > 1);
+        images.sort_by_key(|image| std::cmp::Reverse(image.savings_in_kibibyte()));
+        categories.sort_by_key(|category| std::cmp::Reverse(category.proportional_share_size_in_kibibyte));
+        MultiProcessAnalysis { processes: summaries, shared_images: images, categories }
+    }
+
+    /// Sum of the PSS of all processes, the memory they take together
+    pub fn proportional_share_size_in_kibibyte(&self) -> u64 {
+        self.processes.iter().map(|process| process.proportional_share_size_in_kibibyte).sum()
+    }
+
+    pub fn savings_in_kibibyte(&self) -> u64 {
+        self.shared_images.iter().map(|image| image.savings_in_kibibyte()).sum()
+    }
+}
+
+const PROCESS_TABLE_SEPARATOR: &str = "|----------------------------------------------------------|------------|------------|------------|\n";
+const SHARED_IMAGE_TABLE_SEPARATOR: &str = "|----------------------------------------------------------|------------|------------|------------|---------------|\n";
+const COMBINED_CATEGORY_TABLE_SEPARATOR: &str = "|----------------------------------------------------------|------------|------------|------------|------------|\n";
+
+impl Display for MultiProcessAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Processes:")?;
+        PROCESS_TABLE_SEPARATOR.fmt(f)?;
+        format!("| {:56} | {:10} | {:10} | {:10} |\n", "Process", "RSS [KiB]", "PSS [KiB]", "USS [KiB]").fmt(f)?;
+        PROCESS_TABLE_SEPARATOR.fmt(f)?;
+        for process in &self.processes {
+            format!(
+                "| {:56} | {:10} | {:10} | {:10} |\n",
+                process.name.chars().take(56).collect::<String>(),
+                process.resident_set_size_in_kibibyte,
+                process.proportional_share_size_in_kibibyte,
+                process.unique_set_size_in_kibibyte).fmt(f)?;
+        }
+        PROCESS_TABLE_SEPARATOR.fmt(f)?;
+        format!(
+            "| {:56} | {:10} | {:10} | {:10} |\n",
+            "Total",
+            self.processes.iter().map(|process| process.resident_set_size_in_kibibyte).sum::<u64>(),
+            self.proportional_share_size_in_kibibyte(),
+            self.processes.iter().map(|process| process.unique_set_size_in_kibibyte).sum::<u64>()).fmt(f)?;
+        PROCESS_TABLE_SEPARATOR.fmt(f)?;
+        writeln!(f)?;
+
+        writeln!(f, "Shared Images:")?;
+        if self.shared_images.is_empty() {
+            writeln!(f, "No image is mapped by more than one process")?;
+        } else {
+            SHARED_IMAGE_TABLE_SEPARATOR.fmt(f)?;
+            format!("| {:56} | {:10} | {:10} | {:10} | {:13} |\n", "Image", "#Processes", "RSS [KiB]", "PSS [KiB]", "Savings [KiB]").fmt(f)?;
+            SHARED_IMAGE_TABLE_SEPARATOR.fmt(f)?;
+            for image in &self.shared_images {
+                format!(
+                    "| {:56} | {:10} | {:10} | {:10} | {:13} |\n",
+                    image.name,
+                    image.processes,
+                    image.resident_set_size_in_kibibyte,
+                    image.proportional_share_size_in_kibibyte,
+                    image.savings_in_kibibyte()).fmt(f)?;
+            }
+            SHARED_IMAGE_TABLE_SEPARATOR.fmt(f)?;
+            writeln!(f, "Sharing the images saves {} KiB", self.savings_in_kibibyte())?;
+        }
+        writeln!(f)?;
+
+        writeln!(f, "Categories of all Processes:")?;
+        COMBINED_CATEGORY_TABLE_SEPARATOR.fmt(f)?;
+        format!("| {:56} | {:10} | {:10} | {:10} | {:10} |\n", "Category", "#Processes", "RSS [KiB]", "PSS [KiB]", "USS [KiB]").fmt(f)?;
+        COMBINED_CATEGORY_TABLE_SEPARATOR.fmt(f)?;
+        for category in &self.categories {
+            format!(
+                "| {:56} | {:10} | {:10} | {:10} | {:10} |\n",
+                category.name,
+                category.processes,
+                category.resident_set_size_in_kibibyte,
+                category.proportional_share_size_in_kibibyte,
+                category.unique_set_size_in_kibibyte).fmt(f)?;
+        }
+        COMBINED_CATEGORY_TABLE_SEPARATOR.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod multi_process_tests {
+    use super::*;
+    use crate::pmap_analyzer::{CategorySortKey, PMapCategory};
+
+    #[test]
+    fn unique_memory_and_shared_images() {
+        let library = |inode: u64, resident_set_size_in_kibibyte: u64, proportional_share_size_in_kibibyte: u64| PMap {
+            inode,
+            size_in_kibibyte: resident_set_size_in_kibibyte,
+            resident_set_size_in_kibibyte,
+            proportional_share_size_in_kibibyte,
+            shared_clean_in_kibibyte: resident_set_size_in_kibibyte,
+            mapping_kind: MappingKind::File(FileInfo::new("/usr/share/dotnet/shared/libcoreclr.so")),
+            ..Default::default()
+        };
+        let heap = |resident_set_size_in_kibibyte: u64| PMap {
+            size_in_kibibyte: resident_set_size_in_kibibyte,
+            resident_set_size_in_kibibyte,
+            proportional_share_size_in_kibibyte: resident_set_size_in_kibibyte,
+            private_dirty_in_kibibyte: resident_set_size_in_kibibyte,
+            mapping_kind: MappingKind::AnonymousPrivate(None),
+            ..Default::default()
+        };
+        let categorize = |pages: Vec<PMap>| PMapCategory::get_categories_from_memory_pages(
+            PMapVec(pages),
+            &|page| if page.inode == 0 { "Anonymous".to_string() } else { "libcoreclr.so".to_string() },
+            CategorySortKey::Pss).unwrap();
+        let analysis = MultiProcessAnalysis::analyze(vec![
+            ("1 dotnet".to_string(), categorize(vec![library(42, 6000, 3000), heap(1000)])),
+            ("2 dotnet".to_string(), categorize(vec![library(42, 6000, 3000), heap(500)])),
+            ("3 dotnet".to_string(), categorize(vec![library(43, 100, 100)])),
+        ]);
+
+        assert_eq!(analysis.processes[0], ProcessSummary {
+            name: "1 dotnet".to_string(),
+            resident_set_size_in_kibibyte: 7000,
+            proportional_share_size_in_kibibyte: 4000,
+            unique_set_size_in_kibibyte: 1000,
+        });
+        assert_eq!(analysis.shared_images.len(), 1);
+        assert_eq!((analysis.shared_images[0].processes, analysis.shared_images[0].savings_in_kibibyte()), (2, 6000));
+        let categories = analysis.categories.iter().map(|category| (category.name.as_str(), category.processes, category.proportional_share_size_in_kibibyte)).collect::<Vec<_>>();
+        assert_eq!(categories, vec![("libcoreclr.so", 3, 6100), ("Anonymous", 2, 1500)]);
+    }
+}
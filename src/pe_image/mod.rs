@@ -0,0 +1,371 @@
+use std::error::Error;
+use std::fmt::Display;
+
+use crate::file_info::FileInfo;
+use crate::images::{LoadedImageVec, SegmentKind};
+
+// Layout of a managed assembly (see ECMA-335 II.25 and the ReadyToRun format of the runtime):
+// DOS header ("MZ", offset of the PE header at 0x3c) -> "PE\0\0" -> COFF header -> optional header with the data
+// directories -> section table. The CLI header (data directory 14) points to the metadata and, for ReadyToRun
+// images, to the READYTORUN_HEADER ("RTR") with the precompiled code. A composite image contains the precompiled
+// code of several component assemblies and exports its READYTORUN_HEADER as "RTR_HEADER" instead of a CLI header.
+
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+const EXPORT_DIRECTORY: usize = 0;
+const CLI_HEADER_DIRECTORY: usize = 14;
+// offset of the ManagedNativeHeader directory in the CLI header
+const MANAGED_NATIVE_HEADER_OFFSET: usize = 64;
+const READY_TO_RUN_SIGNATURE: u32 = 0x00525452;
+const READY_TO_RUN_FLAG_COMPONENT: u32 = 0x20;
+const COMPOSITE_EXPORT_NAME: &[u8] = b"RTR_HEADER";
+
+/// Kind of code of a PE image
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AssemblyKind {
+    // only IL, all methods are JIT compiled
+    IlOnly,
+    // IL with precompiled code (crossgen)
+    ReadyToRun,
+    // IL whose precompiled code is part of a composite image
+    CompositeComponent,
+    // precompiled code of several assemblies without own metadata
+    Composite,
+    // PE image without CLI header
+    Native,
+}
+
+impl Display for AssemblyKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssemblyKind::IlOnly => "IL-only".fmt(f),
+            AssemblyKind::ReadyToRun => "ReadyToRun".fmt(f),
+            AssemblyKind::CompositeComponent => "ReadyToRun (Composite Component)".fmt(f),
+            AssemblyKind::Composite => "ReadyToRun (Composite)".fmt(f),
+            AssemblyKind::Native => "Native".fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PeSection {
+    pub name: String,
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    pub pointer_to_raw_data: u32,
+    pub size_of_raw_data: u32,
+}
+
+/// Headers of a PE/COFF image that are needed to classify it and to map file offsets to sections
+#[derive(Debug, PartialEq)]
+pub struct PeImage {
+    pub kind: AssemblyKind,
+    pub size_of_headers: u32,
+    pub sections: Vec<PeSection>,
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, Box<dyn Error>> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| format!("PE image truncated at offset 0x{:x}", offset).into())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Box<dyn Error>> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| format!("PE image truncated at offset 0x{:x}", offset).into())
+}
+
+impl PeImage {
+    pub fn read(file_info: &FileInfo) -> Result<Self, Box<dyn Error>> {
+        PeImage::parse(&file_info.read()?)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if !bytes.starts_with(b"MZ") {
+            return Err("Missing DOS signature".into());
+        }
+        let pe_header = read_u32(bytes, 0x3c)? as usize;
+        if bytes.get(pe_header..pe_header + 4) != Some(b"PE\0\0".as_slice()) {
+            return Err("Missing PE signature".into());
+        }
+        let coff_header = pe_header + 4;
+        let number_of_sections = read_u16(bytes, coff_header + 2)? as usize;
+        let size_of_optional_header = read_u16(bytes, coff_header + 16)? as usize;
+        let optional_header = coff_header + 20;
+        let (number_of_directories, directories) = match read_u16(bytes, optional_header)? {
+            PE32_MAGIC => (read_u32(bytes, optional_header + 92)? as usize, optional_header + 96),
+            PE32_PLUS_MAGIC => (read_u32(bytes, optional_header + 108)? as usize, optional_header + 112),
+            magic => return Err(format!("Unknown optional header magic 0x{:x}", magic).into()),
+        };
+        let size_of_headers = read_u32(bytes, optional_header + 60)?;
+
+        let section_table = optional_header + size_of_optional_header;
+        let mut sections = Vec::with_capacity(number_of_sections);
+        for index in 0..number_of_sections {
+            let section = section_table + index * 40;
+            let name = bytes.get(section..section + 8).ok_or("PE section table truncated")?;
+            sections.push(PeSection {
+                name: String::from_utf8_lossy(name).trim_end_matches('\0').to_string(),
+                virtual_size: read_u32(bytes, section + 8)?,
+                virtual_address: read_u32(bytes, section + 12)?,
+                size_of_raw_data: read_u32(bytes, section + 16)?,
+                pointer_to_raw_data: read_u32(bytes, section + 20)?,
+            });
+        }
+
+        let mut image = PeImage { kind: AssemblyKind::Native, size_of_headers, sections };
+        let directory = |index: usize| -> Result<Option<(u32, u32)>, Box<dyn Error>> {
+            if index >= number_of_directories {
+                return Ok(None);
+            }
+            let rva = read_u32(bytes, directories + index * 8)?;
+            let size = read_u32(bytes, directories + index * 8 + 4)?;
+            Ok(if rva == 0 { None } else { Some((rva, size)) })
+        };
+
+        if let Some((cli_header_rva, _)) = directory(CLI_HEADER_DIRECTORY)? {
+            let cli_header = image.rva_to_offset(cli_header_rva).ok_or("CLI header outside of the sections")?;
+            let managed_native_header = read_u32(bytes, cli_header + MANAGED_NATIVE_HEADER_OFFSET)?;
+            image.kind = match image.rva_to_offset(managed_native_header) {
+                Some(header) if managed_native_header != 0 && read_u32(bytes, header)? == READY_TO_RUN_SIGNATURE => {
+                    if read_u32(bytes, header + 8)? & READY_TO_RUN_FLAG_COMPONENT != 0 {
+                        AssemblyKind::CompositeComponent
+                    } else {
+                        AssemblyKind::ReadyToRun
+                    }
+                }
+                _ => AssemblyKind::IlOnly,
+            };
+        } else if let Some((export_rva, _)) = directory(EXPORT_DIRECTORY)? {
+            if image.exports(bytes, export_rva)?.contains(&COMPOSITE_EXPORT_NAME) {
+                image.kind = AssemblyKind::Composite;
+            }
+        }
+        Ok(image)
+    }
+
+    // None for RVAs outside of the sections, also if the header values of a broken file overflow
+    fn rva_to_offset(&self, rva: u32) -> Option<usize> {
+        self.sections
+            .iter()
+            .find(|section| {
+                section.virtual_address <= rva
+                    && section.virtual_address.checked_add(section.virtual_size.max(section.size_of_raw_data)).is_some_and(|end| rva < end)
+            })
+            .and_then(|section| (rva - section.virtual_address).checked_add(section.pointer_to_raw_data))
+            .map(|offset| offset as usize)
+    }
+
+    // names of the exported symbols
+    fn exports<'a>(&self, bytes: &'a [u8], export_rva: u32) -> Result<Vec<&'a [u8]>, Box<dyn Error>> {
+        let export_directory = self.rva_to_offset(export_rva).ok_or("Export directory outside of the sections")?;
+        let number_of_names = read_u32(bytes, export_directory + 24)? as usize;
+        let names = self.rva_to_offset(read_u32(bytes, export_directory + 32)?).ok_or("Export names outside of the sections")?;
+        // no capacity from the header, a broken file could ask for gigabytes
+        let mut exports = Vec::new();
+        for index in 0..number_of_names {
+            let name = self.rva_to_offset(read_u32(bytes, names + index * 4)?).ok_or("Export name outside of the sections")?;
+            let name = bytes.get(name..).ok_or("Export name outside of the file")?;
+            exports.push(name.split(|byte| *byte == 0).next().unwrap_or(name));
+        }
+        Ok(exports)
+    }
+
+    /// Name of the section containing the file offset, `headers` for the offsets in front of the sections
+    pub fn section_at(&self, offset: u64) -> Option<&str> {
+        self.sections_in(offset, 1).last().copied()
+    }
+
+    /// Names of the sections overlapping the file range [offset, offset + size)
+    pub fn sections_in(&self, offset: u64, size: u64) -> Vec<&str> {
+        let mut names = Vec::new();
+        if offset < self.size_of_headers as u64 {
+            names.push("headers");
+        }
+        for section in &self.sections {
+            let start = section.pointer_to_raw_data as u64;
+            let end = start + section.size_of_raw_data as u64;
+            if start < offset + size && offset < end {
+                names.push(section.name.as_str());
+            }
+        }
+        names
+    }
+}
+
+/// Mapped `.dll` with the result of reading its PE headers from disk
+#[derive(Debug, PartialEq)]
+pub struct ManagedAssembly {
+    pub path: String,
+    // None if the file could not be found or read
+    pub kind: Option<AssemblyKind>,
+    pub mapped_size_in_kibibyte: u64,
+    pub resident_set_size_in_kibibyte: u64,
+    // sections covered by the file-backed mappings
+    pub mapped_sections: Vec<String>,
+}
+
+impl ManagedAssembly {
+    pub fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+}
+
+pub struct ManagedAssemblyVec(pub Vec<ManagedAssembly>);
+
+impl ManagedAssemblyVec {
+    /// Reads the PE headers of all mapped `.dll` files, the files are looked up below the sysroot, at their path or in the application folder
+    pub fn analyze(images: &LoadedImageVec, sysroot: Option<&str>, application_folder: Option<&str>) -> Self {
+        let mut assemblies: Vec<ManagedAssembly> = Vec::new();
+        for image in images.0.iter().filter(|image| image.path.ends_with(".dll")) {
+            let index = match assemblies.iter().position(|assembly| assembly.path == image.path) {
+                Some(index) => index,
+                None => {
+                    assemblies.push(ManagedAssembly {
+                        path: image.path.clone(),
+                        kind: None,
+                        mapped_size_in_kibibyte: 0,
+                        resident_set_size_in_kibibyte: 0,
+                        mapped_sections: Vec::new(),
+                    });
+                    assemblies.len() - 1
+                }
+            };
+            let assembly = &mut assemblies[index];
+            assembly.mapped_size_in_kibibyte += image.size_in_kibibyte();
+            assembly.resident_set_size_in_kibibyte += image.resident_set_size_in_kibibyte();
+
+            let Some(pe_image) = FileInfo::new(&image.path)
+                .resolve(sysroot, application_folder)
+                .and_then(|file_info| PeImage::read(&file_info).ok()) else {
+                continue;
+            };
+            assembly.kind = Some(pe_image.kind);
+            for segment in image.segments.iter().filter(|segment| !matches!(segment.kind, SegmentKind::Bss | SegmentKind::Reserved)) {
+                for name in pe_image.sections_in(segment.offset, segment.size_in_kibibyte * 1024) {
+                    if !assembly.mapped_sections.iter().any(|mapped| mapped == name) {
+                        assembly.mapped_sections.push(name.to_string());
+                    }
+                }
+            }
+        }
+        ManagedAssemblyVec(assemblies)
+    }
+
+    /// Assemblies without precompiled code, their methods have to be JIT compiled
+    pub fn il_only(&self) -> impl Iterator<Item = &ManagedAssembly> {
+        self.0.iter().filter(|assembly| assembly.kind == Some(AssemblyKind::IlOnly))
+    }
+}
+
+const ASSEMBLY_TABLE_SEPARATOR: &str = "|----------------------------------------------------------|----------------------------------|-------------------|------------|------------------------------------------|\n";
+
+impl Display for ManagedAssemblyVec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut assemblies = self.0.iter().collect::<Vec<_>>();
+        // IL-only assemblies first, as they cause JIT work
+        assemblies.sort_by_key(|assembly| (assembly.kind != Some(AssemblyKind::IlOnly), std::cmp::Reverse(assembly.mapped_size_in_kibibyte)));
+
+        ASSEMBLY_TABLE_SEPARATOR.fmt(f)?;
+        format!("| {:56} | {:32} | {:17} | {:10} | {:40} |\n", "Assembly", "Kind", "Mapped Size [KiB]", "RSS [KiB]", "Mapped Sections").fmt(f)?;
+        ASSEMBLY_TABLE_SEPARATOR.fmt(f)?;
+        for assembly in assemblies {
+            let kind = assembly.kind.map(|kind| kind.to_string()).unwrap_or("File not found".to_string());
+            format!(
+                "| {:56} | {:32} | {:17} | {:10} | {:40} |\n",
+                assembly.name(),
+                kind,
+                assembly.mapped_size_in_kibibyte,
+                assembly.resident_set_size_in_kibibyte,
+                assembly.mapped_sections.join(", ")).fmt(f)?;
+        }
+        ASSEMBLY_TABLE_SEPARATOR.fmt(f)?;
+
+        let not_found = self.0.iter().filter(|assembly| assembly.kind.is_none()).count();
+        writeln!(
+            f,
+            "IL-only Assemblies (JIT compiled): {} (Mapped: {} KiB)",
+            self.il_only().count(),
+            self.il_only().map(|assembly| assembly.mapped_size_in_kibibyte).sum::<u64>())?;
+        if not_found > 0 {
+            writeln!(f, "Assemblies not found on disk: {} (use --sysroot or --application-folder)", not_found)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod pe_image_tests {
+    use super::*;
+
+    // PE32+ image with a single .text section at file offset 0x200 (RVA 0x2000) containing the CLI header
+    // and, if flags are given, a READYTORUN_HEADER at file offset 0x300
+    fn assembly(ready_to_run_flags: Option<u32>) -> Vec<u8> {
+        let mut bytes = vec![0u8; 0x400];
+        let mut write = |offset: usize, value: &[u8]| bytes[offset..offset + value.len()].copy_from_slice(value);
+        write(0, b"MZ");
+        write(0x3c, &0x40u32.to_le_bytes());
+        write(0x40, b"PE\0\0");
+        write(0x46, &1u16.to_le_bytes());
+        write(0x54, &240u16.to_le_bytes());
+        write(0x58, &PE32_PLUS_MAGIC.to_le_bytes());
+        write(0x58 + 60, &0x200u32.to_le_bytes());
+        write(0x58 + 108, &16u32.to_le_bytes());
+        write(0x58 + 112 + 14 * 8, &0x2000u32.to_le_bytes());
+        write(0x58 + 112 + 14 * 8 + 4, &72u32.to_le_bytes());
+        write(0x148, b".text\0\0\0");
+        write(0x148 + 8, &0x1000u32.to_le_bytes());
+        write(0x148 + 12, &0x2000u32.to_le_bytes());
+        write(0x148 + 16, &0x200u32.to_le_bytes());
+        write(0x148 + 20, &0x200u32.to_le_bytes());
+        write(0x200, &72u32.to_le_bytes());
+        if let Some(flags) = ready_to_run_flags {
+            write(0x200 + 64, &0x2100u32.to_le_bytes());
+            write(0x300, &READY_TO_RUN_SIGNATURE.to_le_bytes());
+            write(0x308, &flags.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn detect_ready_to_run() {
+        assert_eq!(PeImage::parse(&assembly(None)).unwrap().kind, AssemblyKind::IlOnly);
+        assert_eq!(PeImage::parse(&assembly(Some(0))).unwrap().kind, AssemblyKind::ReadyToRun);
+        assert_eq!(PeImage::parse(&assembly(Some(READY_TO_RUN_FLAG_COMPONENT))).unwrap().kind, AssemblyKind::CompositeComponent);
+        assert!(PeImage::parse(b"MZ").is_err());
+        assert!(PeImage::parse(b"\x7fELF").is_err());
+    }
+
+    #[test]
+    fn reject_overflowing_headers() {
+        // raw data pointer, which overflows with the offset of the CLI header in the section
+        let mut bytes = assembly(None);
+        bytes[0x148 + 20..0x148 + 24].copy_from_slice(&0xffffffffu32.to_le_bytes());
+        bytes[0x58 + 112 + 14 * 8..0x58 + 112 + 14 * 8 + 4].copy_from_slice(&0x2100u32.to_le_bytes());
+        assert!(PeImage::parse(&bytes).is_err());
+        // section reaching beyond the 32 bit address space
+        let mut bytes = assembly(None);
+        bytes[0x148 + 8..0x148 + 12].copy_from_slice(&0xffffffffu32.to_le_bytes());
+        assert!(PeImage::parse(&bytes).is_err());
+        // native image whose export directory claims 4 billion names
+        let mut bytes = assembly(None);
+        bytes[0x58 + 112 + 14 * 8..0x58 + 112 + 14 * 8 + 4].copy_from_slice(&0u32.to_le_bytes());
+        bytes[0x58 + 112..0x58 + 112 + 4].copy_from_slice(&0x2000u32.to_le_bytes());
+        bytes[0x200 + 24..0x200 + 28].copy_from_slice(&0xffffffffu32.to_le_bytes());
+        bytes[0x200 + 32..0x200 + 36].copy_from_slice(&0x2100u32.to_le_bytes());
+        assert!(PeImage::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn file_offsets_to_sections() {
+        let image = PeImage::parse(&assembly(None)).unwrap();
+        assert_eq!(image.sections_in(0x10, 0x10), vec!["headers"]);
+        assert_eq!(image.sections_in(0x250, 0x10), vec![".text"]);
+        assert!(image.sections_in(0x400, 0x10).is_empty());
+        assert_eq!(image.sections_in(0, 4096), vec!["headers", ".text"]);
+        assert_eq!(image.section_at(0x250), Some(".text"));
+    }
+}
@@ -0,0 +1,372 @@
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::io::Error as ioError;
+use std::str::FromStr;
+
+use crate::file_info::FileInfo;
+use crate::pmap::*;
+
+// Sample lines of /tmp/perf-<PID>.map written by the runtime with DOTNET_PerfMapEnabled=1
+// 7F6E842C5000 1A0 instance void [System.Private.CoreLib] System.Threading.ExecutionContext::RunInternal(class System.Threading.ExecutionContext,class System.Threading.ContextCallback,object)[OptimizedTier1]
+// 7F6E842C6000 80 stub<1> AllocateTemporaryEntryPoints<PRECODE_FIXUP>
+// and of /tmp/perfinfo-<PID>.map
+// ImageLoad;/usr/share/dotnet/shared/Microsoft.NETCore.App/7.0.5/System.Private.CoreLib.dll;{6a9e5e1f-...};
+
+/// One JIT'd method (or stub) of a perf map
+#[derive(Debug, PartialEq, Clone)]
+pub struct PerfMapEntry {
+    pub address: u64,
+    pub size_in_byte: u64,
+    pub name: String,
+}
+
+impl PerfMapEntry {
+    /// First address after the method
+    pub fn end_address(&self) -> u64 {
+        self.address.saturating_add(self.size_in_byte)
+    }
+
+    /// Assembly of the method as written by the runtime in brackets (e.g. `[System.Private.CoreLib]`)
+    pub fn assembly(&self) -> String {
+        if self.name.starts_with("stub<") {
+            return "[stubs]".to_string();
+        }
+        match self.assembly_token() {
+            Some((start, end)) => self.name[start + 1..end].to_string(),
+            None => "[unknown]".to_string(),
+        }
+    }
+
+    /// Namespace of the type declaring the method
+    pub fn namespace(&self) -> String {
+        if self.name.starts_with("stub<") {
+            return "[stubs]".to_string();
+        }
+        let type_and_method = match self.assembly_token() {
+            Some((_, end)) => &self.name[end + 1..],
+            None => self.name.as_str(),
+        };
+        let type_and_method = type_and_method.trim_start();
+        let type_name = match type_and_method.find("::") {
+            Some(position) => &type_and_method[..position],
+            None => return "[unknown]".to_string(),
+        };
+        let type_name = type_name.split(['`', '<', '[']).next().unwrap_or(type_name);
+        match type_name.rfind('.') {
+            Some(position) => type_name[..position].to_string(),
+            None => "[global]".to_string(),
+        }
+    }
+
+    // position of the brackets around the assembly name, which is followed by the type name
+    fn assembly_token(&self) -> Option<(usize, usize)> {
+        let mut search_from = 0;
+        while let Some(start) = self.name[search_from..].find('[').map(|position| position + search_from) {
+            let end = self.name[start..].find(']').map(|position| position + start)?;
+            let preceded_by_space = start == 0 || self.name[..start].ends_with(' ');
+            if preceded_by_space && self.name[end + 1..].starts_with(' ') {
+                return Some((start, end));
+            }
+            search_from = end;
+        }
+        None
+    }
+}
+
+impl FromStr for PerfMapEntry {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(3, ' ');
+
+        let address = parts.next().ok_or("Can't parse address")?;
+        let address = u64::from_str_radix(address, 16).map_err(|_| "Can't parse address")?;
+
+        let size_in_byte = parts.next().ok_or("Can't parse size")?;
+        let size_in_byte = u64::from_str_radix(size_in_byte, 16).map_err(|_| "Can't parse size")?;
+
+        let name = parts.next().unwrap_or("").trim().to_string();
+
+        Ok(PerfMapEntry { address, size_in_byte, name })
+    }
+}
+
+/// Content of a perf map file, sorted by address
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct PerfMap(pub Vec<PerfMapEntry>);
+
+impl PerfMap {
+    pub fn parse_perf_map<P: Into<std::path::PathBuf>>(path: P) -> Result<PerfMap, Box<dyn Error>> {
+        let perf_map = FileInfo::new(path);
+        if !perf_map.exists() {
+            return Err(ioError::new(std::io::ErrorKind::NotFound, "File not found").into());
+        }
+
+        let mut entries = Vec::new();
+        for (line_number, line) in perf_map.read_to_string()?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue; // skip empty lines
+            }
+            let entry = PerfMapEntry::from_str(line).map_err(|e| format!("{} (line {})", e, line_number + 1))?;
+            entries.push(entry);
+        }
+        entries.sort_by_key(|entry| entry.address);
+
+        Ok(PerfMap(entries))
+    }
+
+    /// Returns all methods that overlap the memory page, including a method that starts in the page before
+    pub fn methods_in(&self, page: &PMap) -> &[PerfMapEntry] {
+        let mut first = self.0.partition_point(|entry| entry.address < page.address);
+        if first > 0 && self.0[first - 1].end_address() > page.address {
+            first -= 1;
+        }
+        let last = self.0.partition_point(|entry| entry.address < page.end_address());
+        &self.0[first..last]
+    }
+
+    /// Method containing the address
+    pub fn find(&self, address: u64) -> Option<&PerfMapEntry> {
+        let count = self.0.partition_point(|entry| entry.address <= address);
+        self.0[..count].last().filter(|entry| address < entry.end_address())
+    }
+
+    pub fn total_size_in_byte(&self) -> u64 {
+        self.0.iter().map(|entry| entry.size_in_byte).sum()
+    }
+}
+
+/// Images listed in a perfinfo file, by file name without extension
+#[derive(Debug, PartialEq, Default)]
+pub struct PerfInfo(pub HashMap<String, String>);
+
+impl PerfInfo {
+    pub fn parse_perf_info<P: Into<std::path::PathBuf>>(path: P) -> Result<PerfInfo, Box<dyn Error>> {
+        let perf_info = FileInfo::new(path);
+        if !perf_info.exists() {
+            return Err(ioError::new(std::io::ErrorKind::NotFound, "File not found").into());
+        }
+
+        let mut images = HashMap::new();
+        for line in perf_info.read_to_string()?.lines() {
+            let mut parts = line.trim().split(';');
+            if parts.next() != Some("ImageLoad") {
+                continue;
+            }
+            if let Some(path) = parts.next() {
+                let image = FileInfo::new(path);
+                let name = image.name();
+                let name = name.strip_suffix(".dll").unwrap_or(&name).to_string();
+                images.insert(name, image.full_name());
+            }
+        }
+
+        Ok(PerfInfo(images))
+    }
+}
+
+/// How the JIT'd methods are grouped in the attribution report
+#[derive(Copy, Clone, Debug, PartialEq, Default, ValueEnum)]
+pub enum JitCodeGrouping {
+    #[default]
+    Assembly,
+    Namespace,
+}
+
+/// JIT'd methods of a perf map assigned to the executable memory pages that contain them
+pub struct JitCodeAttribution<'a> {
+    pub perf_map: &'a PerfMap,
+    pub perf_info: Option<&'a PerfInfo>,
+    pub grouping: JitCodeGrouping,
+    pub top_methods: usize,
+    // executable memory page (doublemapper view or anonymous) and the methods inside of it
+    pub code_mappings: Vec<(&'a PMap, &'a [PerfMapEntry])>,
+    // methods that are not part of any executable memory page (e.g. perf map of another snapshot)
+    pub unmapped_methods: usize,
+}
+
+impl<'a> JitCodeAttribution<'a> {
+    pub fn attribute(memory_pages: &'a PMapVec, perf_map: &'a PerfMap, perf_info: Option<&'a PerfInfo>, grouping: JitCodeGrouping, top_methods: usize) -> Self {
+        let mut code_mappings = memory_pages.0
+            .iter()
+            .filter(|page| page.permissions.contains(Permissions::Execute))
+            .filter(|page| page.mapping_kind.is_doublemapper() || matches!(page.mapping_kind, MappingKind::AnonymousPrivate(_) | MappingKind::AnonymousShared(_)))
+            .map(|page| (page, perf_map.methods_in(page)))
+            .collect::<Vec<_>>();
+        code_mappings.sort_by_key(|(page, _)| page.address);
+
+        let mut attribution = JitCodeAttribution {
+            perf_map,
+            perf_info,
+            grouping,
+            top_methods,
+            code_mappings,
+            unmapped_methods: 0,
+        };
+        attribution.unmapped_methods = perf_map.0.len() - attribution.mapped_methods().len();
+        attribution
+    }
+
+    /// Methods inside of the executable memory pages, a method spanning two adjacent pages is returned once
+    pub fn mapped_methods(&self) -> Vec<&'a PerfMapEntry> {
+        let mut methods = self.code_mappings.iter().flat_map(|(_, methods)| methods.iter()).collect::<Vec<_>>();
+        // the pages are sorted by address and don't overlap, so the same method can only follow itself
+        methods.dedup_by_key(|method| method.address);
+        methods
+    }
+
+    /// Number of methods and code size per assembly or namespace, biggest first
+    pub fn code_size_by_group(&self) -> Vec<(String, usize, u64)> {
+        let mut groups: HashMap<String, (usize, u64)> = HashMap::new();
+        for method in self.mapped_methods() {
+            let name = match self.grouping {
+                JitCodeGrouping::Assembly => method.assembly(),
+                JitCodeGrouping::Namespace => method.namespace(),
+            };
+            let group = groups.entry(name).or_default();
+            group.0 += 1;
+            group.1 += method.size_in_byte;
+        }
+        let mut groups = groups.into_iter().map(|(name, (count, size))| (name, count, size)).collect::<Vec<_>>();
+        groups.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        groups
+    }
+
+    /// The biggest methods
+    pub fn biggest_methods(&self) -> Vec<&PerfMapEntry> {
+        let mut methods = self.mapped_methods();
+        methods.sort_by_key(|method| std::cmp::Reverse(method.size_in_byte));
+        methods.truncate(self.top_methods);
+        methods
+    }
+}
+
+impl Display for JitCodeAttribution<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let with_methods = self.code_mappings.iter().filter(|(_, methods)| !methods.is_empty()).count();
+        let without_methods_size: u64 = self.code_mappings.iter().filter(|(_, methods)| methods.is_empty()).map(|(page, _)| page.size_in_kibibyte).sum();
+        writeln!(f, "Methods in Perf Map: {} ({} KiB)", self.perf_map.0.len(), self.perf_map.total_size_in_byte() / 1024)?;
+        writeln!(f, "Methods outside of executable Memory Pages: {}", self.unmapped_methods)?;
+        writeln!(f, "Executable Memory Pages with Methods: {} of {} (without Methods: {} KiB)", with_methods, self.code_mappings.len(), without_methods_size)?;
+        writeln!(f)?;
+
+        let group_title = match self.grouping {
+            JitCodeGrouping::Assembly => "Assembly",
+            JitCodeGrouping::Namespace => "Namespace",
+        };
+        "|----------------------------------------------------------|------------|-----------------|\n".fmt(f)?;
+        format!("| {:56} | {:10} | {:15} |\n", group_title, "Size [KiB]", "#Methods").fmt(f)?;
+        "|----------------------------------------------------------|------------|-----------------|\n".fmt(f)?;
+        for (name, count, size) in self.code_size_by_group() {
+            format!("| {:56} | {:10} | {:15} |\n", name, size / 1024, count).fmt(f)?;
+        }
+        "|----------------------------------------------------------|------------|-----------------|\n".fmt(f)?;
+        if let Some(perf_info) = self.perf_info {
+            if self.grouping == JitCodeGrouping::Assembly {
+                for (name, ..) in self.code_size_by_group() {
+                    if let Some(path) = perf_info.0.get(&name) {
+                        writeln!(f, "{}: {}", name, path)?;
+                    }
+                }
+            }
+        }
+        writeln!(f)?;
+
+        writeln!(f, "Top {} Methods by Size:", self.top_methods)?;
+        "|--------------|------------|--------------------------------------------------------------------------------------------------------------------------------------------------------|\n".fmt(f)?;
+        format!("| {:^12} | {:10} | {:150} |\n", "Address", "Size [B]", "Method").fmt(f)?;
+        "|--------------|------------|--------------------------------------------------------------------------------------------------------------------------------------------------------|\n".fmt(f)?;
+        for method in self.biggest_methods() {
+            format!("| {:12x} | {:10} | {:150} |\n", method.address, method.size_in_byte, method.name).fmt(f)?;
+        }
+        "|--------------|------------|--------------------------------------------------------------------------------------------------------------------------------------------------------|\n".fmt(f)?;
+        Ok(())
+    }
+}
+
+/// Growth of the JIT'd code over several perf map snapshots
+pub struct JitCodeGrowth<'a>(pub Vec<(String, &'a PerfMap)>);
+
+impl Display for JitCodeGrowth<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        "|----------------------------------------------------------|-----------------|------------|-------------|\n".fmt(f)?;
+        format!("| {:56} | {:15} | {:10} | {:11} |\n", "Snapshot", "#Methods", "Size [KiB]", "Delta [KiB]").fmt(f)?;
+        "|----------------------------------------------------------|-----------------|------------|-------------|\n".fmt(f)?;
+        let mut previous: Option<u64> = None;
+        for (name, perf_map) in &self.0 {
+            let size = perf_map.total_size_in_byte() / 1024;
+            let delta = previous.map(|previous| size as i64 - previous as i64).unwrap_or(0);
+            format!("| {:56} | {:15} | {:10} | {:+11} |\n", name, perf_map.0.len(), size, delta).fmt(f)?;
+            previous = Some(size);
+        }
+        "|----------------------------------------------------------|-----------------|------------|-------------|\n".fmt(f)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod perf_map_tests {
+    use super::*;
+
+    #[test]
+    fn perf_map_entry_from_str() {
+        let input = "7F6E842C5000 1A0 instance void [System.Private.CoreLib] System.Threading.ExecutionContext::RunInternal(class System.Threading.ExecutionContext,object)[OptimizedTier1]";
+        let entry = PerfMapEntry::from_str(input).unwrap();
+        assert_eq!(entry.address, 0x7f6e842c5000);
+        assert_eq!(entry.size_in_byte, 0x1a0);
+        assert_eq!(entry.assembly(), "System.Private.CoreLib");
+        assert_eq!(entry.namespace(), "System.Threading");
+    }
+
+    #[test]
+    fn perf_map_entry_of_generic_type_and_stub() {
+        let entry = PerfMapEntry::from_str("1000 10 void [Demo] Demo.Collections.Cache`1[System.__Canon]::Add(!0)[Tier0]").unwrap();
+        assert_eq!(entry.assembly(), "Demo");
+        assert_eq!(entry.namespace(), "Demo.Collections");
+
+        let stub = PerfMapEntry::from_str("2000 80 stub<1> AllocateTemporaryEntryPoints<PRECODE_FIXUP>").unwrap();
+        assert_eq!(stub.assembly(), "[stubs]");
+        assert_eq!(stub.namespace(), "[stubs]");
+    }
+
+    #[test]
+    fn methods_are_attributed_to_code_mappings() {
+        let perf_map = PerfMap(vec![
+            PerfMapEntry::from_str("1000 100 void [A] A.B::C()").unwrap(),
+            PerfMapEntry::from_str("1100 300 void [A] A.B::D()").unwrap(),
+            PerfMapEntry::from_str("9000 10 void [E] E.F::G()").unwrap(),
+        ]);
+        let code = PMap {
+            address: 0x1000,
+            size_in_kibibyte: 4,
+            permissions: Permissions::Read | Permissions::Execute | Permissions::Private,
+            ..Default::default()
+        };
+        let memory_pages = PMapVec(vec![code]);
+
+        let attribution = JitCodeAttribution::attribute(&memory_pages, &perf_map, None, JitCodeGrouping::Assembly, 1);
+        assert_eq!(attribution.unmapped_methods, 1);
+        assert_eq!(attribution.code_size_by_group(), vec![("A".to_string(), 2, 0x400)]);
+        assert_eq!(attribution.biggest_methods()[0].address, 0x1100);
+    }
+
+    #[test]
+    fn method_spanning_two_pages() {
+        let perf_map = PerfMap(vec![
+            PerfMapEntry::from_str("1f00 200 void [A] A.B::C()").unwrap(),
+            PerfMapEntry::from_str("FFFFFFFFFFFFFF00 1000 void [A] A.B::D()").unwrap(),
+        ]);
+        let code = |address| PMap { address, size_in_kibibyte: 4, permissions: Permissions::Read | Permissions::Execute | Permissions::Private, ..Default::default() };
+        let memory_pages = PMapVec(vec![code(0x1000), code(0x2000)]);
+
+        assert_eq!(perf_map.methods_in(&memory_pages.0[1]).len(), 1);
+        assert_eq!(perf_map.0[1].end_address(), u64::MAX);
+        let attribution = JitCodeAttribution::attribute(&memory_pages, &perf_map, None, JitCodeGrouping::Assembly, 5);
+        assert_eq!(attribution.code_mappings.iter().map(|(_, methods)| methods.len()).collect::<Vec<_>>(), vec![1, 1]);
+        assert_eq!(attribution.unmapped_methods, 1);
+        assert_eq!(attribution.code_size_by_group(), vec![("A".to_string(), 1, 0x200)]);
+    }
+}
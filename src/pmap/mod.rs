@@ -105,8 +105,13 @@ impl PMap {
             return Err(ioError::new(std::io::ErrorKind::NotFound, "File not found").into());
         }
 
+        PMap::parse_pmap_text(&pmap_output.read_to_string()?)
+    }
+
+    /// Parses the output of `pmap -XX -p <PID>`, the first line (`<PID>:   <command line>`) is skipped
+    pub fn parse_pmap_text(pmap_output: &str) -> Result<PMapVec, Box<dyn Error>> {
         let mut pmaps = PMapVec(Vec::new());
-        pmap_output.read_to_string()?.lines().skip(1).try_for_each(
+        pmap_output.lines().skip(1).try_for_each(
             |line| -> Result<(), Box<dyn Error>> {
                 let line = line.trim();
                 if line.is_empty() {
@@ -120,11 +125,17 @@ impl PMap {
 
         Ok(pmaps)
     }
+
+    /// First address after the memory page
+    pub fn end_address(&self) -> u64 {
+        self.address + self.size_in_kibibyte * 1024
+    }
 }
 
 impl FromStr for PMap {
     type Err = Box<dyn Error>;
 
+    #[allow(clippy::from_str_radix_10)]
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let s = s.trim();
 
@@ -248,7 +259,7 @@ impl FromStr for PMap {
         let transparent_huge_page_eligible = parts
             .next()
             .ok_or("Can't parse transparent huge page eligible")?;
-        let transparent_huge_page_eligible = transparent_huge_page_eligible == "-1";
+        let transparent_huge_page_eligible = transparent_huge_page_eligible == "1";
 
         let mut virtual_memory_flags = BitFlags::<VirtualMemoryFlags>::empty();
 
@@ -380,6 +391,20 @@ impl Default for PMap {
     }
 }
 
+#[cfg(test)]
+impl PMap {
+    /// Memory page for tests with the permissions as pmap prints them (e.g. `r-xp`), all counters are 0
+    pub fn test_page(address: u64, permissions: &str, size_in_kibibyte: u64, mapping_kind: MappingKind) -> Self {
+        Self {
+            address,
+            permissions: <BitFlags<Permissions> as MyFromStr>::from_str(permissions).unwrap(),
+            size_in_kibibyte,
+            mapping_kind,
+            ..Default::default()
+        }
+    }
+}
+
 impl Display for PMap {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         format!("| {:12x} | {:10} | {:30} | {:40} | {:150} |\n", self.address, self.size_in_kibibyte, self.mapping_kind, self.permissions.my_display(), self.virtual_memory_flags.my_display()).fmt(f)?;
@@ -442,7 +467,7 @@ impl MyFromStr for BitFlags<Permissions> {
         } else if private_or_shared != Some('-') {
             return Err(format!("Can't parse permissions: {}", s).into());
         }
-        if parts.next() != None {
+        if parts.next().is_some() {
             return Err(format!("Can't parse permissions: {}", s).into());
         }
 
@@ -800,7 +825,7 @@ pub enum MappingKind {
     Stack,
     // [vdso]
     VirtualDynamicSharedObject,
-    // [vvar]
+    // [vvar] or [vvar_vclock] (since Linux 6.13)
     VirtualVariables,
     // [vsyscall]
     VirtualSystemCall,
@@ -812,6 +837,16 @@ pub enum MappingKind {
     File(FileInfo),
 }
 
+impl MappingKind {
+    /// Returns true for the memfd views of the JIT double mapping (W^X)
+    pub fn is_doublemapper(&self) -> bool {
+        match self {
+            MappingKind::File(file_info) => file_info.full_name().contains("memfd:doublemapper"),
+            _ => false,
+        }
+    }
+}
+
 impl FromStr for MappingKind {
     type Err = Box<dyn Error>;
 
@@ -826,23 +861,22 @@ impl FromStr for MappingKind {
                 Ok(MappingKind::Stack)
             } else if s == "vdso" {
                 Ok(MappingKind::VirtualDynamicSharedObject)
-            } else if s == "vvar" {
+            } else if s == "vvar" || s == "vvar_vclock" {
                 Ok(MappingKind::VirtualVariables)
             } else if s == "vsyscall" {
                 Ok(MappingKind::VirtualSystemCall)
-            } else if s.starts_with("anon") {
-                let s = &s[4..];
-                if s.starts_with("_shmem:") {
-                    if s.len() > 7 {
-                        Ok(MappingKind::AnonymousShared(Some(s[7..].into())))
+            } else if let Some(s) = s.strip_prefix("anon") {
+                if let Some(name) = s.strip_prefix("_shmem:") {
+                    if !name.is_empty() {
+                        Ok(MappingKind::AnonymousShared(Some(name.into())))
                     } else {
                         Ok(MappingKind::AnonymousShared(None))
                     }
-                } else if s.starts_with(':') {
-                    if s.len() == 1 {
+                } else if let Some(name) = s.strip_prefix(':') {
+                    if name.is_empty() {
                         Ok(MappingKind::AnonymousPrivate(None))
                     } else {
-                        Ok(MappingKind::AnonymousPrivate(Some(s[1..].into())))
+                        Ok(MappingKind::AnonymousPrivate(Some(name.into())))
                     }
                 } else {
                     Err("Invalid mapping kind".into())
@@ -862,20 +896,20 @@ impl FromStr for MappingKind {
 impl Display for MappingKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
-            MappingKind::Heap => format!("Heap").fmt(f),
-            MappingKind::Stack => format!("Stack").fmt(f),
-            MappingKind::VirtualDynamicSharedObject => format!("Virtual Dynamic Shared Object").fmt(f),
-            MappingKind::VirtualVariables => format!("Virtual Variables").fmt(f),
-            MappingKind::VirtualSystemCall => format!("Virtual System Call").fmt(f),
-            MappingKind::AnonymousPrivate(None) => format!("Anonymous Private").fmt(f),
+            MappingKind::Heap => "Heap".fmt(f),
+            MappingKind::Stack => "Stack".fmt(f),
+            MappingKind::VirtualDynamicSharedObject => "Virtual Dynamic Shared Object".fmt(f),
+            MappingKind::VirtualVariables => "Virtual Variables".fmt(f),
+            MappingKind::VirtualSystemCall => "Virtual System Call".fmt(f),
+            MappingKind::AnonymousPrivate(None) => "Anonymous Private".fmt(f),
             MappingKind::AnonymousPrivate(Some(name)) => {
                 format!("Anonymous Private ({})", name).fmt(f)
             }
-            MappingKind::AnonymousShared(None) => format!("Anonymous Shared").fmt(f),
+            MappingKind::AnonymousShared(None) => "Anonymous Shared".fmt(f),
             MappingKind::AnonymousShared(Some(name)) => {
                 format!("Anonymous Shared ({})", name).fmt(f)
             }
-            MappingKind::File(fi) => format!("{}", fi.name()).fmt(f),
+            MappingKind::File(fi) => fi.name().fmt(f),
         }
     }
 }
@@ -904,15 +938,15 @@ impl Display for PMapVec {
             .iter()
             .filter(|a| a.size_in_kibibyte >= MIN_SIZE_TO_DISPLAY)
             .collect::<Vec<_>>();
-        let _ = &pages_to_print.sort_by(|a,b| b.size_in_kibibyte.cmp(&a.size_in_kibibyte));
+        pages_to_print.sort_by_key(|page| std::cmp::Reverse(page.size_in_kibibyte));
 
-        format!("|--------------|------------|--------------------------------|------------------------------------------|--------------------------------------------------------------------------------------------------------------------------------------------------------|\n").fmt(f)?;
+        "|--------------|------------|--------------------------------|------------------------------------------|--------------------------------------------------------------------------------------------------------------------------------------------------------|\n".fmt(f)?;
         format!("| {:^12} | {:^10} | {:^30} | {:^40} | {:150} |\n", "Address", "Size [KiB]", "Mapping Kind", "Permissions", "VM Flags").fmt(f)?;
-        format!("|--------------|------------|--------------------------------|------------------------------------------|--------------------------------------------------------------------------------------------------------------------------------------------------------|\n").fmt(f)?;
+        "|--------------|------------|--------------------------------|------------------------------------------|--------------------------------------------------------------------------------------------------------------------------------------------------------|\n".fmt(f)?;
         for pmap in pages_to_print.iter() {
             pmap.fmt(f)?;
         }
-        format!("|--------------|------------|--------------------------------|------------------------------------------|--------------------------------------------------------------------------------------------------------------------------------------------------------|\n").fmt(f)?;
+        "|--------------|------------|--------------------------------|------------------------------------------|--------------------------------------------------------------------------------------------------------------------------------------------------------|\n".fmt(f)?;
 
         writeln!(f)?;
         Ok(())
@@ -925,10 +959,63 @@ impl Clone for PMapVec {
     }
 }
 
+impl PMapVec {
+    /// Builds an index of the memory pages ordered by address for fast lookups
+    pub fn index(&self) -> PMapIndex<'_> {
+        let mut pages = self.0.iter().collect::<Vec<_>>();
+        pages.sort_by_key(|page| page.address);
+        PMapIndex(pages)
+    }
+}
+
+/// Memory pages ordered by address, the memory pages of a process never overlap
+pub struct PMapIndex<'a>(pub Vec<&'a PMap>);
+
+impl<'a> PMapIndex<'a> {
+    /// Memory page containing the address
+    pub fn find(&self, address: u64) -> Option<&'a PMap> {
+        let count = self.0.partition_point(|page| page.address <= address);
+        self.0[..count].last().copied().filter(|page| address < page.end_address())
+    }
+
+    /// Memory pages completely inside of [start, end)
+    pub fn range(&self, start: u64, end: u64) -> &[&'a PMap] {
+        let first = self.0.partition_point(|page| page.address < start);
+        let last = self.0.partition_point(|page| page.end_address() <= end).max(first);
+        &self.0[first..last]
+    }
+
+    /// Memory pages with at least one byte inside of [start, end)
+    pub fn overlapping(&self, start: u64, end: u64) -> &[&'a PMap] {
+        let first = self.0.partition_point(|page| page.end_address() <= start);
+        let last = self.0.partition_point(|page| page.address < end).max(first);
+        &self.0[first..last]
+    }
+}
+
 #[cfg(test)]
 mod pmap_tests {
     use super::*;
-    use enumflags2::{bitflags, make_bitflags, BitFlags};
+    use enumflags2::{make_bitflags, BitFlags};
+
+    #[test]
+    fn index_find_range_and_overlapping() {
+        let page = |address: u64, size_in_kibibyte: u64| PMap { address, size_in_kibibyte, ..Default::default() };
+        let memory_pages = PMapVec(vec![page(0x3000, 4), page(0x1000, 8), page(0x8000, 4)]);
+        let index = memory_pages.index();
+
+        assert_eq!(index.find(0x1fff).map(|page| page.address), Some(0x1000));
+        assert_eq!(index.find(0x3000).map(|page| page.address), Some(0x3000));
+        assert!(index.find(0x4000).is_none());
+        assert!(index.find(0x0).is_none());
+
+        let addresses = |pages: &[&PMap]| pages.iter().map(|page| page.address).collect::<Vec<_>>();
+        assert_eq!(addresses(index.range(0x1000, 0x4000)), vec![0x1000, 0x3000]);
+        assert_eq!(addresses(index.range(0x2000, 0x9000)), vec![0x3000, 0x8000]);
+        assert!(index.range(0x4000, 0x8000).is_empty());
+        assert_eq!(addresses(index.overlapping(0x2000, 0x3001)), vec![0x1000, 0x3000]);
+        assert!(index.overlapping(0x4000, 0x8000).is_empty());
+    }
 
     #[test]
     fn mapping_kind_from_heap() {
@@ -951,6 +1038,13 @@ mod pmap_tests {
         assert_eq!(result, MappingKind::VirtualDynamicSharedObject);
     }
 
+    #[test]
+    fn mapping_kind_from_vvar_vclock() {
+        let input = "[vvar_vclock]";
+        let result: MappingKind = input.parse().unwrap();
+        assert_eq!(result, MappingKind::VirtualVariables);
+    }
+
     #[test]
     fn mapping_kind_from_anon() {
         let input = "[anon:]";
@@ -1050,6 +1144,7 @@ mod pmap_tests {
     }
 
     #[test]
+    #[allow(non_snake_case)]
     fn vmflags_with_pure_PFN_range() {
         let input = "pf";
         let result = BitFlags::<VirtualMemoryFlags>::from_str(input).unwrap();
@@ -1280,9 +1375,10 @@ mod pmap_tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn pmap_from_str_test() {
         //                      Adresse Zugr  Versatz Gerät   Inode      Size KernelPageSize MMUPageSize    Rss    Pss Pss_Dirty Shared_Clean Shared_Dirty Private_Clean Private_Dirty Referenced Anonymous LazyFree AnonHugePages ShmemPmdMapped FilePmdMapped Shared_Hugetlb Private_Hugetlb Swap SwapPss Locked THPeligible                 VmFlags Zuordnung
-        let input = "7faf68872000 rw-p 02743000  00:01    4128         4              4           4      1      2         3            4            5             6             7          8         9        1             2              3             4              5               6    7       8      9          -1 rd ex sh mr mw me ms sd memfd:doublemapper (deleted)";
+        let input = "7faf68872000 rw-p 02743000  00:01    4128         4              4           4      1      2         3            4            5             6             7          8         9        1             2              3             4              5               6    7       8      9           1 rd ex sh mr mw me ms sd memfd:doublemapper (deleted)";
         let result = PMap::from_str(input).unwrap();
         assert_eq!(result.address, 0x7faf68872000);
         assert_eq!(
@@ -1327,4 +1423,11 @@ mod pmap_tests {
             MappingKind::File(FileInfo::new("memfd:doublemapper (deleted)"))
         );
     }
+
+    #[test]
+    fn pmap_transparent_huge_page_eligible_test() {
+        let line = |eligible: &str| format!("7f6eb4e00000 rw-p 00000000  00:00       0      8192              4           4   2048   2048      2048            0            0             0          2048       2048      2048        0          2048              0             0              0               0    0       0      0           {} rd wr mr mw me ac sd", eligible);
+        assert!(PMap::from_str(&line("1")).unwrap().transparent_huge_page_eligible);
+        assert!(!PMap::from_str(&line("0")).unwrap().transparent_huge_page_eligible);
+    }
 }
@@ -1,41 +1,110 @@
 use std::fmt::Display;
 
+use clap::ValueEnum;
+
 use crate::pmap::*;
 
 #[derive(Debug, PartialEq)]
 pub struct PMapCategory {
     pub name: String,
     pub total_size_in_kibibyte: u64,
+    // sum of the RSS of all pages of the category
+    pub resident_set_size_in_kibibyte: u64,
+    // sum of the PSS of all pages of the category
+    pub proportional_share_size_in_kibibyte: u64,
+    pub shared_clean_in_kibibyte: u64,
+    pub shared_dirty_in_kibibyte: u64,
+    pub private_clean_in_kibibyte: u64,
+    pub private_dirty_in_kibibyte: u64,
+    pub anonymous_in_kibibyte: u64,
+    pub swap_in_kibibyte: u64,
+    pub locked_in_kibibyte: u64,
     pub pages: Vec<PMap>,
 }
 
+/// Column of the category overview that is used to order the categories (always descending)
+#[derive(Copy, Clone, Debug, PartialEq, Default, ValueEnum)]
+pub enum CategorySortKey {
+    // virtual size of all pages
+    Size,
+    // resident set size
+    Rss,
+    // proportional share size
+    #[default]
+    Pss,
+    SharedClean,
+    SharedDirty,
+    PrivateClean,
+    PrivateDirty,
+    Anonymous,
+    Swap,
+    Locked,
+    // number of memory pages
+    Pages,
+}
+
 impl PMapCategory {
     fn new(name: String) -> Self {
         Self {
             name,
             total_size_in_kibibyte: 0,
+            resident_set_size_in_kibibyte: 0,
+            proportional_share_size_in_kibibyte: 0,
+            shared_clean_in_kibibyte: 0,
+            shared_dirty_in_kibibyte: 0,
+            private_clean_in_kibibyte: 0,
+            private_dirty_in_kibibyte: 0,
+            anonymous_in_kibibyte: 0,
+            swap_in_kibibyte: 0,
+            locked_in_kibibyte: 0,
             pages: Vec::new(),
         }
     }
 
     fn add_page(&mut self, page: PMap) {
         self.total_size_in_kibibyte += page.size_in_kibibyte;
+        self.resident_set_size_in_kibibyte += page.resident_set_size_in_kibibyte;
+        self.proportional_share_size_in_kibibyte += page.proportional_share_size_in_kibibyte;
+        self.shared_clean_in_kibibyte += page.shared_clean_in_kibibyte;
+        self.shared_dirty_in_kibibyte += page.shared_dirty_in_kibibyte;
+        self.private_clean_in_kibibyte += page.private_clean_in_kibibyte;
+        self.private_dirty_in_kibibyte += page.private_dirty_in_kibibyte;
+        self.anonymous_in_kibibyte += page.anonymous_in_kibibyte;
+        self.swap_in_kibibyte += page.swap_in_kibibyte;
+        self.locked_in_kibibyte += page.locked_in_kibibyte;
         self.pages.push(page);
     }
 
+    /// Returns the aggregated value of the column described by `key`
+    pub fn value_of(&self, key: CategorySortKey) -> u64 {
+        match key {
+            CategorySortKey::Size => self.total_size_in_kibibyte,
+            CategorySortKey::Rss => self.resident_set_size_in_kibibyte,
+            CategorySortKey::Pss => self.proportional_share_size_in_kibibyte,
+            CategorySortKey::SharedClean => self.shared_clean_in_kibibyte,
+            CategorySortKey::SharedDirty => self.shared_dirty_in_kibibyte,
+            CategorySortKey::PrivateClean => self.private_clean_in_kibibyte,
+            CategorySortKey::PrivateDirty => self.private_dirty_in_kibibyte,
+            CategorySortKey::Anonymous => self.anonymous_in_kibibyte,
+            CategorySortKey::Swap => self.swap_in_kibibyte,
+            CategorySortKey::Locked => self.locked_in_kibibyte,
+            CategorySortKey::Pages => self.pages.len() as u64,
+        }
+    }
+
     pub fn get_categories_from_memory_pages(
         memory_pages: PMapVec,
-        get_custom_category_name: &dyn Fn(MappingKind) -> String)
+        get_custom_category_name: &dyn Fn(&PMap) -> String,
+        sort_by: CategorySortKey)
         -> Result<PMapCategoryVec, String> {
 
         let mut categories: PMapCategoryVec = PMapCategoryVec(Vec::new());
         for page in memory_pages.0{
             let category_name: Result<String, String> = match page.mapping_kind {
-                MappingKind::File(_) => Ok(get_custom_category_name(page.mapping_kind.clone())),
-                MappingKind::AnonymousPrivate(None) => Ok("Anonymous".to_string()),
-                MappingKind::AnonymousPrivate(Some(_)) => Ok(get_custom_category_name(page.mapping_kind.clone())),
+                MappingKind::File(_) => Ok(get_custom_category_name(&page)),
+                MappingKind::AnonymousPrivate(_) => Ok(get_custom_category_name(&page)),
                 MappingKind::AnonymousShared(None) => Ok("Anonymous".to_string()),
-                MappingKind::AnonymousShared(Some(_)) => Ok(get_custom_category_name(page.mapping_kind.clone())),
+                MappingKind::AnonymousShared(Some(_)) => Ok(get_custom_category_name(&page)),
                 MappingKind::Heap => Ok("[heap]".to_string()),
                 MappingKind::Stack => Ok("[stack]".to_string()),
                 MappingKind::VirtualVariables => Ok("[vvar]".to_string()),
@@ -55,7 +124,7 @@ impl PMapCategory {
             category.add_page(page);
         }
 
-        categories.0.sort_by(|a, b| b.total_size_in_kibibyte.cmp(&a.total_size_in_kibibyte));
+        categories.sort_by(sort_by);
         Ok(categories)
     }
 }
@@ -63,31 +132,127 @@ impl PMapCategory {
 
 impl Display for PMapCategory {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        format!("| {:56} | {:10} | {:15} |", self.name, self.total_size_in_kibibyte, self.pages.len()).fmt(f)
+        format!(
+            "| {:56} | {:10} | {:10} | {:10} | {:12} | {:12} | {:13} | {:13} | {:10} | {:10} | {:10} | {:15} |",
+            self.name,
+            self.total_size_in_kibibyte,
+            self.resident_set_size_in_kibibyte,
+            self.proportional_share_size_in_kibibyte,
+            self.shared_clean_in_kibibyte,
+            self.shared_dirty_in_kibibyte,
+            self.private_clean_in_kibibyte,
+            self.private_dirty_in_kibibyte,
+            self.anonymous_in_kibibyte,
+            self.swap_in_kibibyte,
+            self.locked_in_kibibyte,
+            self.pages.len()).fmt(f)
     }
 }
 
 pub struct PMapCategoryVec(pub Vec<PMapCategory>);
 
+const CATEGORY_TABLE_SEPARATOR: &str = "|----------------------------------------------------------|------------|------------|------------|--------------|--------------|---------------|---------------|------------|------------|------------|-----------------|\n";
+
+impl PMapCategoryVec {
+    /// Orders the categories descending by the given column, categories with equal values keep their order
+    pub fn sort_by(&mut self, key: CategorySortKey) {
+        self.0.sort_by_key(|category| std::cmp::Reverse(category.value_of(key)));
+    }
+}
+
 impl Display for PMapCategoryVec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut total_size: u64 = 0;
+        let mut total = PMapCategory::new(String::new());
         let mut total_pages: u64 = 0;
-        format!("|----------------------------------------------------------|------------|-----------------|\n").fmt(f)?;
-        format!("| {:56} | {:10} | {:15} |\n", "Category", "Size [KiB]", "#Memory Pages").fmt(f)?;
-        format!("|----------------------------------------------------------|------------|-----------------|\n").fmt(f)?;
+        CATEGORY_TABLE_SEPARATOR.fmt(f)?;
+        format!(
+            "| {:56} | {:10} | {:10} | {:10} | {:12} | {:12} | {:13} | {:13} | {:10} | {:10} | {:10} | {:15} |\n",
+            "Category", "Size [KiB]", "RSS [KiB]", "PSS [KiB]", "Shared Clean", "Shared Dirty", "Private Clean", "Private Dirty", "Anonymous", "Swap [KiB]", "Locked", "#Memory Pages").fmt(f)?;
+        CATEGORY_TABLE_SEPARATOR.fmt(f)?;
 
-        for category in &self.0[0..self.0.len() - 1] {
+        for category in &self.0 {
             category.fmt(f)?;
             writeln!(f)?;
-            total_size += category.total_size_in_kibibyte;
+            total.total_size_in_kibibyte += category.total_size_in_kibibyte;
+            total.resident_set_size_in_kibibyte += category.resident_set_size_in_kibibyte;
+            total.proportional_share_size_in_kibibyte += category.proportional_share_size_in_kibibyte;
+            total.shared_clean_in_kibibyte += category.shared_clean_in_kibibyte;
+            total.shared_dirty_in_kibibyte += category.shared_dirty_in_kibibyte;
+            total.private_clean_in_kibibyte += category.private_clean_in_kibibyte;
+            total.private_dirty_in_kibibyte += category.private_dirty_in_kibibyte;
+            total.anonymous_in_kibibyte += category.anonymous_in_kibibyte;
+            total.swap_in_kibibyte += category.swap_in_kibibyte;
+            total.locked_in_kibibyte += category.locked_in_kibibyte;
             total_pages += category.pages.len() as u64;
         }
-        format!("|----------------------------------------------------------|------------|-----------------|\n").fmt(f)?;
-        format!("| {:56} | {:10} | {:15} |\n","", total_size, total_pages).fmt(f)?;
-        format!("|----------------------------------------------------------|------------|-----------------|\n").fmt(f)?;
+        CATEGORY_TABLE_SEPARATOR.fmt(f)?;
+        format!(
+            "| {:56} | {:10} | {:10} | {:10} | {:12} | {:12} | {:13} | {:13} | {:10} | {:10} | {:10} | {:15} |\n",
+            "",
+            total.total_size_in_kibibyte,
+            total.resident_set_size_in_kibibyte,
+            total.proportional_share_size_in_kibibyte,
+            total.shared_clean_in_kibibyte,
+            total.shared_dirty_in_kibibyte,
+            total.private_clean_in_kibibyte,
+            total.private_dirty_in_kibibyte,
+            total.anonymous_in_kibibyte,
+            total.swap_in_kibibyte,
+            total.locked_in_kibibyte,
+            total_pages).fmt(f)?;
+        CATEGORY_TABLE_SEPARATOR.fmt(f)?;
         writeln!(f)?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod pmap_analyzer_tests {
+    use super::*;
+
+    fn page(mapping_kind: MappingKind, size: u64, pss: u64, private_dirty: u64) -> PMap {
+        PMap {
+            mapping_kind,
+            size_in_kibibyte: size,
+            resident_set_size_in_kibibyte: pss,
+            proportional_share_size_in_kibibyte: pss,
+            private_dirty_in_kibibyte: private_dirty,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn categories_aggregate_all_columns() {
+        let memory_pages = PMapVec(vec![
+            page(MappingKind::Heap, 100, 40, 30),
+            page(MappingKind::Heap, 200, 60, 10),
+        ]);
+
+        let categories = PMapCategory::get_categories_from_memory_pages(memory_pages, &|_| String::new(), CategorySortKey::Pss).unwrap();
+        assert_eq!(categories.0.len(), 1);
+        assert_eq!(categories.0[0].total_size_in_kibibyte, 300);
+        assert_eq!(categories.0[0].resident_set_size_in_kibibyte, 100);
+        assert_eq!(categories.0[0].proportional_share_size_in_kibibyte, 100);
+        assert_eq!(categories.0[0].private_dirty_in_kibibyte, 40);
+    }
+
+    #[test]
+    fn categories_sorted_by_requested_key() {
+        let memory_pages = PMapVec(vec![
+            page(MappingKind::Heap, 1000, 10, 5),
+            page(MappingKind::Stack, 10, 20, 1),
+            page(MappingKind::VirtualDynamicSharedObject, 100, 5, 50),
+        ]);
+
+        let mut categories = PMapCategory::get_categories_from_memory_pages(memory_pages, &|_| String::new(), CategorySortKey::Pss).unwrap();
+        let names = |categories: &PMapCategoryVec| categories.0.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&categories), vec!["[stack]", "[heap]", "[vdso]"]);
+
+        categories.sort_by(CategorySortKey::PrivateDirty);
+        assert_eq!(names(&categories), vec!["[vdso]", "[heap]", "[stack]"]);
+
+        categories.sort_by(CategorySortKey::Size);
+        assert_eq!(names(&categories), vec!["[heap]", "[vdso]", "[stack]"]);
+    }
+}
@@ -0,0 +1,323 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::path::Path;
+
+use crate::multi_process::short_command_line;
+use crate::pmap::*;
+
+// folder of the processes of this machine, pmap can only read these
+pub const LIVE_PROC_FOLDER: &str = "/proc";
+
+// the runtime library mapped by every .NET process, also by apphost executables that are not called dotnet
+const CORECLR: &str = "libcoreclr.so";
+
+/// .NET process found in `/proc`
+#[derive(Debug, PartialEq, Clone)]
+pub struct DotnetProcess {
+    pub pid: u32,
+    // closest ancestor that is a .NET process too, 0 for the roots of the tree
+    pub parent_pid: u32,
+    pub command_line: String,
+    pub threads: u64,
+    // level in the process tree
+    pub depth: usize,
+}
+
+/// Process id and parent process id from `/proc/<PID>/stat` (the name in parentheses may contain blanks)
+fn parse_stat(stat: &str) -> Option<(u32, u32)> {
+    let (pid, rest) = stat.split_once(" (")?;
+    let (_, rest) = rest.rsplit_once(") ")?;
+    let parent_pid = rest.split_whitespace().nth(1)?;
+    Some((pid.trim().parse().ok()?, parent_pid.parse().ok()?))
+}
+
+fn parse_threads(status: &str) -> Option<u64> {
+    status.lines().find_map(|line| line.strip_prefix("Threads:")).and_then(|threads| threads.trim().parse().ok())
+}
+
+/// The .NET host is `dotnet` itself or an apphost executable with the runtime mapped
+fn is_dotnet(executable: &str, arguments: &[&str], maps: &str) -> bool {
+    let file_name = |path: &str| path.rsplit('/').next().unwrap_or(path).to_string();
+    file_name(executable) == "dotnet"
+        || arguments.first().is_some_and(|argument| file_name(argument) == "dotnet")
+        || maps.lines().any(|line| line.ends_with(CORECLR))
+}
+
+/// .NET processes ordered like a tree, each parent before its children
+pub struct DotnetProcessTree(pub Vec<DotnetProcess>);
+
+impl DotnetProcessTree {
+    /// Walks the process folders of `/proc` (or a copy of it), processes that can't be read are skipped
+    pub fn scan(proc_folder: &str) -> Self {
+        let folder = Path::new(proc_folder);
+        let mut parents = Vec::new();
+        let mut processes = Vec::new();
+        for entry in std::fs::read_dir(folder).into_iter().flatten().flatten() {
+            let path = entry.path();
+            let read = |name: &str| std::fs::read_to_string(path.join(name)).unwrap_or_default();
+            let Some((pid, parent_pid)) = parse_stat(&read("stat")) else {
+                continue;
+            };
+            parents.push((pid, parent_pid));
+            let cmdline = read("cmdline");
+            let arguments = cmdline.split('\0').filter(|argument| !argument.is_empty()).collect::<Vec<_>>();
+            let executable = std::fs::read_link(path.join("exe")).map(|exe| exe.display().to_string()).unwrap_or_default();
+            if !is_dotnet(&executable, &arguments, &read("maps")) {
+                continue;
+            }
+            processes.push(DotnetProcess {
+                pid,
+                parent_pid,
+                command_line: short_command_line(arguments.into_iter()),
+                threads: parse_threads(&read("status")).unwrap_or(0),
+                depth: 0,
+            });
+        }
+        DotnetProcessTree::from_processes(processes, &parents)
+    }
+
+    /// Links each process to its closest .NET ancestor (e.g. through a shell in between) and orders them depth first
+    fn from_processes(mut processes: Vec<DotnetProcess>, parents: &[(u32, u32)]) -> Self {
+        let pids = processes.iter().map(|process| process.pid).collect::<Vec<_>>();
+        for process in processes.iter_mut() {
+            let mut ancestor = process.parent_pid;
+            // the loop ends at init (parent 0), the number of steps guards against cycles of a captured folder
+            for _ in 0..parents.len() {
+                if ancestor == 0 || pids.contains(&ancestor) {
+                    break;
+                }
+                ancestor = parents.iter().find(|(pid, _)| *pid == ancestor).map(|(_, parent)| *parent).unwrap_or(0);
+            }
+            process.parent_pid = if pids.contains(&ancestor) { ancestor } else { 0 };
+        }
+        processes.sort_by_key(|process| process.pid);
+
+        let mut ordered = Vec::new();
+        let mut stack = processes
+            .iter()
+            .filter(|process| process.parent_pid == 0)
+            .rev()
+            .map(|process| (process.pid, 0))
+            .collect::<Vec<_>>();
+        while let Some((pid, depth)) = stack.pop() {
+            let Some(process) = processes.iter().find(|process| process.pid == pid) else {
+                continue;
+            };
+            ordered.push(DotnetProcess { depth, ..process.clone() });
+            stack.extend(processes.iter().filter(|child| child.parent_pid == pid).rev().map(|child| (child.pid, depth + 1)));
+        }
+        DotnetProcessTree(ordered)
+    }
+}
+
+/// Runs `pmap -XX -p <PID>` for the live `/proc`, the same output the analysis reads from a file.
+/// pmap only sees the processes of this machine, for a copy of `/proc` the captured `<PID>/smaps` is read instead
+pub fn read_memory_pages(proc_folder: &str, pid: u32) -> Result<PMapVec, Box<dyn Error>> {
+    if Path::new(proc_folder) != Path::new(LIVE_PROC_FOLDER) {
+        let path = Path::new(proc_folder).join(pid.to_string()).join("smaps");
+        let smaps = std::fs::read_to_string(&path).map_err(|error| format!("Can't read {}: {}", path.display(), error))?;
+        return PMap::parse_pmap_text(&smaps_columns(pid, &smaps));
+    }
+    let output = std::process::Command::new("pmap").args(["-XX", "-p", &pid.to_string()]).output()?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string().into());
+    }
+    PMap::parse_pmap_text(&known_columns(&String::from_utf8_lossy(&output.stdout)))
+}
+
+// columns in front of VmFlags the parser reads, in this order
+const PARSED_COLUMNS: [&str; 27] = [
+    "Address", "Perm", "Offset", "Device", "Inode", "Size", "KernelPageSize", "MMUPageSize", "Rss", "Pss", "Pss_Dirty",
+    "Shared_Clean", "Shared_Dirty", "Private_Clean", "Private_Dirty", "Referenced", "Anonymous", "LazyFree", "AnonHugePages",
+    "ShmemPmdMapped", "FilePmdMapped", "Shared_Hugetlb", "Private_Hugetlb", "Swap", "SwapPss", "Locked", "THPeligible",
+];
+
+/// Removes the columns newer kernels added (e.g. `KSM`, `ProtectionKey`) using the header of the pmap output,
+/// the header and the totals at the end are dropped, the first line (`<PID>:   <command line>`) is kept
+fn known_columns(pmap_output: &str) -> String {
+    let mut lines = pmap_output.lines();
+    let mut result = lines.next().unwrap_or_default().to_string();
+    result.push('\n');
+    let Some(header) = lines.next() else {
+        return result;
+    };
+    let columns = header.split_whitespace().take_while(|column| *column != "VmFlags").collect::<Vec<_>>();
+    for line in lines {
+        // memory pages start with the address followed by the permissions like r-xp
+        let mut tokens = line.split_whitespace();
+        let is_memory_page = tokens.next().is_some_and(|address| u64::from_str_radix(address, 16).is_ok())
+            && tokens.next().is_some_and(|permissions| permissions.len() == 4);
+        if !is_memory_page {
+            continue;
+        }
+        let mut rest = line.trim_start();
+        for column in &columns {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            if PARSED_COLUMNS.contains(column) {
+                result.push_str(&rest[..end]);
+                result.push(' ');
+            }
+            rest = rest[end..].trim_start();
+        }
+        result.push_str(rest);
+        result.push('\n');
+    }
+    result
+}
+
+// memory pages of smaps start with the address range like 7f6eb4e00000-7f6eb5600000
+fn is_smaps_header(line: &str) -> bool {
+    line.split_whitespace()
+        .next()
+        .and_then(|range| range.split_once('-'))
+        .is_some_and(|(start, end)| u64::from_str_radix(start, 16).is_ok() && u64::from_str_radix(end, 16).is_ok())
+}
+
+/// Converts `/proc/<PID>/smaps` to the columns of `pmap -XX` the parser reads, fields older kernels don't print are 0
+fn smaps_columns(pid: u32, smaps: &str) -> String {
+    let mut result = format!("{}:\n", pid);
+    let mut lines = smaps.lines().peekable();
+    while let Some(header) = lines.next() {
+        if !is_smaps_header(header) {
+            continue;
+        }
+        // address range, permissions, offset, device and inode, the rest is the mapping
+        let mut columns = Vec::new();
+        let mut rest = header.trim_start();
+        for _ in 0..5 {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            columns.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+        columns[0] = columns[0].split('-').next().unwrap_or_default();
+
+        let mut fields = Vec::new();
+        let mut virtual_memory_flags = "";
+        while let Some(line) = lines.next_if(|line| !is_smaps_header(line)) {
+            match line.split_once(':') {
+                Some(("VmFlags", flags)) => virtual_memory_flags = flags.trim(),
+                Some((field, value)) => fields.push((field, value.split_whitespace().next().unwrap_or("0"))),
+                None => {}
+            }
+        }
+        for column in &PARSED_COLUMNS[columns.len()..] {
+            columns.push(fields.iter().find(|(field, _)| field == column).map(|(_, value)| *value).unwrap_or("0"));
+        }
+        columns.push(virtual_memory_flags);
+        columns.push(rest);
+        result.push_str(columns.join(" ").trim_end());
+        result.push('\n');
+    }
+    result
+}
+
+/// Memory of a .NET process, None if its memory pages could not be read
+#[derive(Debug, PartialEq, Default)]
+pub struct DotnetProcessMemory {
+    pub proportional_share_size_in_kibibyte: u64,
+    pub gc_reservation_in_kibibyte: Option<u64>,
+    pub jit_code_in_kibibyte: u64,
+}
+
+/// One row per .NET process, indented by the level in the process tree
+pub struct ProcessOverview(pub Vec<(DotnetProcess, Option<DotnetProcessMemory>)>);
+
+const PROCESS_OVERVIEW_TABLE_SEPARATOR: &str = "|----------|----------------------------------------------------------|------------|----------------------|----------------|----------|\n";
+
+impl Display for ProcessOverview {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return writeln!(f, "No .NET processes found");
+        }
+        PROCESS_OVERVIEW_TABLE_SEPARATOR.fmt(f)?;
+        format!("| {:>8} | {:56} | {:10} | {:20} | {:14} | {:8} |\n", "PID", "Command Line", "PSS [KiB]", "GC Reservation [KiB]", "JIT Code [KiB]", "#Threads").fmt(f)?;
+        PROCESS_OVERVIEW_TABLE_SEPARATOR.fmt(f)?;
+        for (process, memory) in &self.0 {
+            let command_line = format!("{}{}", "  ".repeat(process.depth), process.command_line).chars().take(56).collect::<String>();
+            let (pss, gc_reservation, jit_code) = match memory {
+                Some(memory) => (
+                    memory.proportional_share_size_in_kibibyte.to_string(),
+                    memory.gc_reservation_in_kibibyte.map(|size| size.to_string()).unwrap_or_default(),
+                    memory.jit_code_in_kibibyte.to_string()),
+                None => ("n/a".to_string(), "n/a".to_string(), "n/a".to_string()),
+            };
+            format!("| {:>8} | {:56} | {:>10} | {:>20} | {:>14} | {:>8} |\n", process.pid, command_line, pss, gc_reservation, jit_code, process.threads).fmt(f)?;
+        }
+        PROCESS_OVERVIEW_TABLE_SEPARATOR.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod process_scan_tests {
+    use super::*;
+
+    #[test]
+    fn detect_dotnet_processes_and_build_tree() {
+        assert_eq!(parse_stat("4711 (dotnet (x) y) S 42 4711 42 0 -1"), Some((4711, 42)));
+        assert_eq!(parse_threads("Name:\tdotnet\nThreads:\t23\n"), Some(23));
+        assert!(is_dotnet("/usr/share/dotnet/dotnet", &["dotnet", "app.dll"], ""));
+        assert!(is_dotnet("/app/MyService", &["/app/MyService"], "7f00 r-xp 00000000 08:01 42 /usr/share/dotnet/shared/Microsoft.NETCore.App/7.0.0/libcoreclr.so"));
+        assert!(!is_dotnet("/usr/bin/bash", &["bash"], ""));
+
+        let pmap_output = "4711:   dotnet app.dll\n\
+            Address Perm Offset Device Inode Size KernelPageSize MMUPageSize Rss Pss Pss_Dirty Shared_Clean Shared_Dirty Private_Clean Private_Dirty Referenced Anonymous KSM LazyFree AnonHugePages ShmemPmdMapped FilePmdMapped Shared_Hugetlb Private_Hugetlb Swap SwapPss Locked THPeligible ProtectionKey VmFlags Mapping\n\
+            7faf68872000 r-xs 02743000 00:01 4128 4 4 4 4 4 0 0 0 4 0 4 0 7 0 0 0 0 0 0 0 0 0 1 9 rd ex sh mr mw me ms sd memfd:doublemapper (deleted)\n\
+            ==== ==== =====\n\
+            4 4 4 4 KB\n";
+        let memory_pages = PMap::parse_pmap_text(&known_columns(pmap_output)).unwrap();
+        assert_eq!(memory_pages.0.len(), 1);
+        // KSM (7) and ProtectionKey (9) are left out
+        assert_eq!((memory_pages.0[0].lazy_free_in_kibibyte, memory_pages.0[0].transparent_huge_page_eligible), (0, true));
+        assert!(memory_pages.0[0].mapping_kind.is_doublemapper());
+
+        let process = |pid: u32, parent_pid: u32| DotnetProcess { pid, parent_pid, command_line: format!("dotnet {}", pid), threads: 1, depth: 0 };
+        // 30 is started by 10 through the shell 20
+        let parents = [(1, 0), (10, 1), (20, 10), (30, 20), (40, 10), (50, 1)];
+        let tree = DotnetProcessTree::from_processes(vec![process(50, 1), process(30, 20), process(40, 10), process(10, 1)], &parents);
+        let order = tree.0.iter().map(|process| (process.pid, process.parent_pid, process.depth)).collect::<Vec<_>>();
+        assert_eq!(order, vec![(10, 0, 0), (30, 10, 1), (40, 10, 1), (50, 0, 0)]);
+    }
+
+    #[test]
+    fn read_smaps_of_a_captured_proc_folder() {
+        // the PID of this test process, pmap would succeed for it if it was run for a captured folder
+        let pid = std::process::id();
+        let folder = std::env::temp_dir().join(format!("pmap_proc_{}", pid));
+        std::fs::create_dir_all(folder.join(pid.to_string())).unwrap();
+        std::fs::write(folder.join(pid.to_string()).join("smaps"), "\
+            7faf68872000-7faf68873000 r-xs 02743000 00:01 4128                       /memfd:doublemapper (deleted)\n\
+            Size:                  4 kB\n\
+            KernelPageSize:        4 kB\n\
+            MMUPageSize:           4 kB\n\
+            Rss:                   4 kB\n\
+            Pss:                   3 kB\n\
+            Private_Clean:         4 kB\n\
+            Referenced:            4 kB\n\
+            THPeligible:    1\n\
+            ProtectionKey:         0\n\
+            VmFlags: rd ex sh mr mw me ms sd\n\
+            7ffc1b9d4000-7ffc1b9f5000 rw-p 00000000 00:00 0                          [stack]\n\
+            Size:                132 kB\n\
+            Rss:                  20 kB\n\
+            Pss:                  20 kB\n\
+            Anonymous:            20 kB\n\
+            THPeligible:    0\n\
+            VmFlags: rd wr mr mw me gd ac\n").unwrap();
+
+        let folder_name = folder.to_string_lossy().to_string();
+        let memory_pages = read_memory_pages(&folder_name, pid);
+        let missing = read_memory_pages(&folder_name, pid + 1);
+        std::fs::remove_dir_all(&folder).unwrap();
+        let memory_pages = memory_pages.unwrap();
+        assert_eq!(memory_pages.0.len(), 2);
+        let doublemapper = &memory_pages.0[0];
+        assert_eq!((doublemapper.address, doublemapper.offset, doublemapper.inode), (0x7faf68872000, 0x02743000, 4128));
+        assert_eq!((doublemapper.proportional_share_size_in_kibibyte, doublemapper.private_clean_in_kibibyte), (3, 4));
+        assert!(doublemapper.transparent_huge_page_eligible);
+        assert!(doublemapper.mapping_kind.is_doublemapper());
+        let stack = &memory_pages.0[1];
+        assert_eq!((stack.size_in_kibibyte, stack.anonymous_in_kibibyte, stack.mapping_kind.clone()), (132, 20, MappingKind::Stack));
+        assert!(stack.virtual_memory_flags.contains(VirtualMemoryFlags::GrowsDown));
+        assert!(missing.is_err_and(|error| error.to_string().contains("smaps")));
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt::Display;
+
+use crate::pmap::*;
+use crate::pmap_analyzer::PMapCategoryVec;
+
+// files in these locations live in shared memory (tmpfs), their pages can only be swapped out like anonymous memory
+const SHARED_MEMORY_FILES: [&str; 4] = ["memfd:", "dev/shm/", "run/shm/", "SYSV"];
+
+/// Resident memory of a category split by what the kernel can do with it under memory pressure (assuming no swap)
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ReclaimableMemory {
+    pub name: String,
+    pub resident_set_size_in_kibibyte: u64,
+    // Shared_Clean and Private_Clean of file mappings, dropped and read again from the file when needed
+    pub clean_file_in_kibibyte: u64,
+    // pages released with MADV_FREE, dropped without writing them anywhere
+    pub lazy_free_in_kibibyte: u64,
+    // modified pages of shared file mappings, reclaimable after they are written back to the file
+    pub dirty_file_in_kibibyte: u64,
+    // anonymous, copy-on-write, shared memory and locked pages
+    pub unreclaimable_in_kibibyte: u64,
+    // referenced clean pages of executable file mappings, dropping them causes page faults right away
+    pub hot_code_in_kibibyte: u64,
+}
+
+/// Returns true for mappings of files in shared memory (memfd, `/dev/shm`, System V)
+pub fn is_shared_memory_file(page: &PMap) -> bool {
+    match &page.mapping_kind {
+        MappingKind::File(file_info) => {
+            let full_name = file_info.full_name();
+            let full_name = full_name.trim_start_matches('/');
+            SHARED_MEMORY_FILES.iter().any(|prefix| full_name.starts_with(prefix))
+        }
+        _ => false,
+    }
+}
+
+impl ReclaimableMemory {
+    fn add(&mut self, page: &PMap) {
+        let resident_set_size = page.resident_set_size_in_kibibyte;
+        self.resident_set_size_in_kibibyte += resident_set_size;
+        // locked pages stay in RAM regardless of their kind
+        let locked = page.locked_in_kibibyte.min(resident_set_size);
+        let mut clean_file = 0;
+        let mut dirty_file = 0;
+        let lazy_free = page.lazy_free_in_kibibyte.min(resident_set_size - locked);
+        if matches!(page.mapping_kind, MappingKind::File(_)) && !is_shared_memory_file(page) {
+            clean_file = (page.shared_clean_in_kibibyte + page.private_clean_in_kibibyte).min(resident_set_size - locked - lazy_free);
+            // dirty pages of private file mappings are anonymous copies, only shared mappings write back to the file
+            if page.virtual_memory_flags.contains(VirtualMemoryFlags::Shared) {
+                dirty_file = (page.shared_dirty_in_kibibyte + page.private_dirty_in_kibibyte).min(resident_set_size - locked - lazy_free - clean_file);
+            }
+            if page.permissions.contains(Permissions::Execute) {
+                self.hot_code_in_kibibyte += page.referenced_in_kibibyte.min(clean_file);
+            }
+        }
+        self.clean_file_in_kibibyte += clean_file;
+        self.lazy_free_in_kibibyte += lazy_free;
+        self.dirty_file_in_kibibyte += dirty_file;
+        self.unreclaimable_in_kibibyte += resident_set_size - clean_file - lazy_free - dirty_file;
+    }
+
+    /// Memory the kernel can drop, either right away or after writeback
+    pub fn reclaimable_in_kibibyte(&self) -> u64 {
+        self.clean_file_in_kibibyte + self.lazy_free_in_kibibyte + self.dirty_file_in_kibibyte
+    }
+}
+
+/// Reclaimable memory per category and the minimum working set of the process
+pub struct ReclaimableAnalysis {
+    // categories ordered by the reclaimable memory
+    pub categories: Vec<ReclaimableMemory>,
+    pub total: ReclaimableMemory,
+}
+
+impl ReclaimableAnalysis {
+    pub fn analyze(categories: &PMapCategoryVec) -> Self {
+        let mut total = ReclaimableMemory { name: "Total".to_string(), ..Default::default() };
+        let mut reclaimable = categories.0
+            .iter()
+            .map(|category| {
+                let mut memory = ReclaimableMemory { name: category.name.clone(), ..Default::default() };
+                for page in &category.pages {
+                    memory.add(page);
+                    total.add(page);
+                }
+                memory
+            })
+            .filter(|memory| memory.resident_set_size_in_kibibyte > 0)
+            .collect::<Vec<_>>();
+        reclaimable.sort_by_key(|memory| (std::cmp::Reverse(memory.reclaimable_in_kibibyte()), std::cmp::Reverse(memory.resident_set_size_in_kibibyte)));
+        ReclaimableAnalysis { categories: reclaimable, total }
+    }
+
+    /// Resident memory that stays after the kernel dropped everything it can
+    pub fn minimum_working_set_in_kibibyte(&self) -> u64 {
+        self.total.unreclaimable_in_kibibyte
+    }
+
+    /// Minimum working set plus the code in use, below this the process mostly waits for page faults
+    pub fn practical_working_set_in_kibibyte(&self) -> u64 {
+        self.total.unreclaimable_in_kibibyte + self.total.hot_code_in_kibibyte
+    }
+}
+
+const RECLAIMABLE_TABLE_SEPARATOR: &str = "|----------------------------------------------------------|------------|------------------|-----------------|------------------|---------------------|----------------|\n";
+
+fn fmt_reclaimable(f: &mut std::fmt::Formatter<'_>, memory: &ReclaimableMemory) -> std::fmt::Result {
+    format!(
+        "| {:56} | {:10} | {:16} | {:15} | {:16} | {:19} | {:14} |\n",
+        memory.name,
+        memory.resident_set_size_in_kibibyte,
+        memory.clean_file_in_kibibyte,
+        memory.lazy_free_in_kibibyte,
+        memory.dirty_file_in_kibibyte,
+        memory.unreclaimable_in_kibibyte,
+        memory.hot_code_in_kibibyte).fmt(f)
+}
+
+impl Display for ReclaimableAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        RECLAIMABLE_TABLE_SEPARATOR.fmt(f)?;
+        format!(
+            "| {:56} | {:10} | {:16} | {:15} | {:16} | {:19} | {:14} |\n",
+            "Category", "RSS [KiB]", "Clean File [KiB]", "LazyFree [KiB]", "Dirty File [KiB]", "Unreclaimable [KiB]", "Hot Code [KiB]").fmt(f)?;
+        RECLAIMABLE_TABLE_SEPARATOR.fmt(f)?;
+        for memory in &self.categories {
+            fmt_reclaimable(f, memory)?;
+        }
+        RECLAIMABLE_TABLE_SEPARATOR.fmt(f)?;
+        fmt_reclaimable(f, &self.total)?;
+        RECLAIMABLE_TABLE_SEPARATOR.fmt(f)?;
+        writeln!(
+            f,
+            "Reclaimable: {} KiB of {} KiB RSS ({} KiB need writeback first)",
+            self.total.reclaimable_in_kibibyte(),
+            self.total.resident_set_size_in_kibibyte,
+            self.total.dirty_file_in_kibibyte)?;
+        writeln!(f, "Minimum Working Set: {} KiB (unreclaimable memory without swap)", self.minimum_working_set_in_kibibyte())?;
+        writeln!(f, "Practical Working Set: {} KiB (including the referenced code, which would be read again right away)", self.practical_working_set_in_kibibyte())
+    }
+}
+
+#[cfg(test)]
+mod reclaimable_tests {
+    use super::*;
+    use crate::file_info::FileInfo;
+    use crate::pmap_analyzer::{CategorySortKey, PMapCategory};
+
+    #[test]
+    fn split_resident_memory_by_reclaimability() {
+        let file = |path: &str| MappingKind::File(FileInfo::new(path));
+        let pages = PMapVec(vec![
+            PMap { resident_set_size_in_kibibyte: 400, private_clean_in_kibibyte: 400, referenced_in_kibibyte: 200, ..PMap::test_page(0, "r-xp", 1024, file("/usr/lib/libcoreclr.so")) },
+            PMap { resident_set_size_in_kibibyte: 40, private_dirty_in_kibibyte: 40, ..PMap::test_page(0, "rw-p", 1024, file("/usr/lib/libcoreclr.so")) },
+            PMap {
+                resident_set_size_in_kibibyte: 210,
+                private_clean_in_kibibyte: 10,
+                private_dirty_in_kibibyte: 200,
+                referenced_in_kibibyte: 5,
+                virtual_memory_flags: VirtualMemoryFlags::Shared.into(),
+                ..PMap::test_page(0, "rw-s", 1024, file("/var/cache/app.db"))
+            },
+            PMap {
+                resident_set_size_in_kibibyte: 64,
+                private_dirty_in_kibibyte: 64,
+                virtual_memory_flags: VirtualMemoryFlags::Shared.into(),
+                ..PMap::test_page(0, "rw-s", 1024, file("/memfd:doublemapper (deleted)"))
+            },
+            PMap {
+                resident_set_size_in_kibibyte: 300,
+                private_dirty_in_kibibyte: 300,
+                lazy_free_in_kibibyte: 100,
+                ..PMap::test_page(0, "rw-p", 1024, MappingKind::AnonymousPrivate(None))
+            },
+        ]);
+        let categories = PMapCategory::get_categories_from_memory_pages(pages, &|_| "All".to_string(), CategorySortKey::Pss).unwrap();
+
+        let analysis = ReclaimableAnalysis::analyze(&categories);
+        let total = &analysis.total;
+        assert_eq!(
+            (total.clean_file_in_kibibyte, total.lazy_free_in_kibibyte, total.dirty_file_in_kibibyte, total.unreclaimable_in_kibibyte, total.hot_code_in_kibibyte),
+            (410, 100, 200, 304, 200));
+        assert_eq!(analysis.minimum_working_set_in_kibibyte(), 304);
+        assert_eq!(analysis.practical_working_set_in_kibibyte(), 504);
+    }
+}
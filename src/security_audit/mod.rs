@@ -0,0 +1,321 @@
+use std::fmt::Display;
+
+use clap::ValueEnum;
+
+use crate::dotnet_regions::{ClassifiedRegionVec, Confidence, DotnetRegionKind};
+use crate::pmap::*;
+
+// world-writable locations, code mapped from there may have been planted by another process
+const WRITABLE_LOCATIONS: [&str; 4] = ["/tmp/", "/var/tmp/", "/dev/shm/", "/run/shm/"];
+
+/// How serious a finding is
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Low => "Low".fmt(f),
+            Severity::Medium => "Medium".fmt(f),
+            Severity::High => "High".fmt(f),
+        }
+    }
+}
+
+/// Output format of the audit
+#[derive(Copy, Clone, Debug, PartialEq, Default, ValueEnum)]
+pub enum AuditFormat {
+    // table for humans
+    #[default]
+    Text,
+    // array of findings for pipelines
+    Json,
+}
+
+/// Check that reported a memory page
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AuditCheck {
+    WritableExecutable,
+    AnonymousExecutable,
+    SharedWritableFile,
+    StackWithoutGuardPage,
+    ExecutableFromWritableLocation,
+}
+
+impl AuditCheck {
+    /// Identifier used in the machine-readable output
+    pub fn id(&self) -> &'static str {
+        match self {
+            AuditCheck::WritableExecutable => "writable-executable",
+            AuditCheck::AnonymousExecutable => "anonymous-executable",
+            AuditCheck::SharedWritableFile => "shared-writable-file",
+            AuditCheck::StackWithoutGuardPage => "stack-without-guard-page",
+            AuditCheck::ExecutableFromWritableLocation => "executable-from-writable-location",
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            AuditCheck::WritableExecutable => Severity::High,
+            AuditCheck::AnonymousExecutable => Severity::Medium,
+            AuditCheck::SharedWritableFile => Severity::Low,
+            AuditCheck::StackWithoutGuardPage => Severity::Medium,
+            AuditCheck::ExecutableFromWritableLocation => Severity::High,
+        }
+    }
+
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            AuditCheck::WritableExecutable =>
+                "Memory that is writable and executable at the same time allows to inject and run code, for .NET enable W^X (DOTNET_EnableWriteXorExecute=1, default since .NET 7)",
+            AuditCheck::AnonymousExecutable =>
+                "Executable anonymous memory outside of the JIT regions of the runtime may contain injected code or code of another code generator",
+            AuditCheck::SharedWritableFile =>
+                "Writes go straight to the file and are visible to every other process mapping it, the file content must not be trusted",
+            AuditCheck::StackWithoutGuardPage =>
+                "Mapping with the size of a thread stack without guard page in front of it, a stack overflow silently overwrites the memory below",
+            AuditCheck::ExecutableFromWritableLocation =>
+                "Code is mapped from a world-writable location, where other processes can place or replace files",
+        }
+    }
+}
+
+/// Memory page reported by a check
+#[derive(Debug, PartialEq)]
+pub struct AuditFinding<'a> {
+    pub check: AuditCheck,
+    pub page: &'a PMap,
+}
+
+fn permissions(page: &PMap) -> String {
+    [
+        (Permissions::Read, 'r'),
+        (Permissions::Write, 'w'),
+        (Permissions::Execute, 'x'),
+    ]
+    .iter()
+    .map(|(permission, c)| if page.permissions.contains(*permission) { *c } else { '-' })
+    .chain(std::iter::once(if page.permissions.contains(Permissions::Shared) { 's' } else { 'p' }))
+    .collect()
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+// the JIT and the loader heaps of the runtime map executable anonymous memory by design, a lone executable page
+// is only guessed to be the runtime's (low confidence) and stays suspicious
+fn is_jit_region(classified_regions: &ClassifiedRegionVec, address: u64) -> bool {
+    classified_regions
+        .find(address)
+        .is_some_and(|region| matches!(region.kind, DotnetRegionKind::ExecutableAllocator | DotnetRegionKind::LoaderHeap) && region.confidence > Confidence::Low)
+}
+
+/// Findings of all checks over the permissions and flags of the memory pages, ordered by severity and address
+pub struct SecurityAudit<'a> {
+    pub findings: Vec<AuditFinding<'a>>,
+    pub format: AuditFormat,
+}
+
+impl<'a> SecurityAudit<'a> {
+    pub fn audit(memory_pages: &'a PMapIndex<'a>, classified_regions: &ClassifiedRegionVec, thread_stack_size_in_kibibyte: u64, format: AuditFormat) -> Self {
+        let mut findings = Vec::new();
+        let pages = &memory_pages.0;
+        for (index, page) in pages.iter().enumerate() {
+            let writable = page.permissions.contains(Permissions::Write);
+            let executable = page.permissions.contains(Permissions::Execute);
+            let anonymous = matches!(page.mapping_kind, MappingKind::AnonymousPrivate(_) | MappingKind::AnonymousShared(_) | MappingKind::Heap | MappingKind::Stack);
+            if writable && executable {
+                findings.push(AuditFinding { check: AuditCheck::WritableExecutable, page });
+            } else if executable && anonymous && !is_jit_region(classified_regions, page.address) {
+                findings.push(AuditFinding { check: AuditCheck::AnonymousExecutable, page });
+            }
+
+            if let MappingKind::File(file_info) = &page.mapping_kind {
+                let full_name = file_info.full_name();
+                let memfd = full_name.trim_start_matches('/').starts_with("memfd:");
+                if writable && page.virtual_memory_flags.contains(VirtualMemoryFlags::Shared) && !memfd {
+                    findings.push(AuditFinding { check: AuditCheck::SharedWritableFile, page });
+                }
+                if executable && WRITABLE_LOCATIONS.iter().any(|location| full_name.starts_with(location)) {
+                    findings.push(AuditFinding { check: AuditCheck::ExecutableFromWritableLocation, page });
+                }
+            }
+
+            // stacks with guard page are classified as thread stacks, the remaining mappings of the stack size are suspicious
+            let stack_sized = page.size_in_kibibyte == thread_stack_size_in_kibibyte
+                && matches!(page.mapping_kind, MappingKind::AnonymousPrivate(None))
+                && writable
+                && !executable
+                && classified_regions.find(page.address).is_none();
+            let guarded = index > 0 && pages[index - 1].end_address() == page.address && pages[index - 1].permissions == Permissions::Private;
+            if stack_sized && !guarded {
+                findings.push(AuditFinding { check: AuditCheck::StackWithoutGuardPage, page });
+            }
+        }
+        findings.sort_by_key(|finding| (std::cmp::Reverse(finding.check.severity()), finding.page.address));
+        SecurityAudit { findings, format }
+    }
+
+    /// Highest severity of all findings
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.findings.iter().map(|finding| finding.check.severity()).max()
+    }
+
+    fn fmt_json(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "[")?;
+        for (index, finding) in self.findings.iter().enumerate() {
+            writeln!(
+                f,
+                "  {{\"check\": {}, \"severity\": {}, \"address\": \"0x{:x}\", \"end_address\": \"0x{:x}\", \"size_in_kibibyte\": {}, \"permissions\": {}, \"mapping\": {}, \"explanation\": {}}}{}",
+                json_string(finding.check.id()),
+                json_string(&finding.check.severity().to_string().to_lowercase()),
+                finding.page.address,
+                finding.page.end_address(),
+                finding.page.size_in_kibibyte,
+                json_string(&permissions(finding.page)),
+                json_string(&mapping_name(finding.page)),
+                json_string(finding.check.explanation()),
+                if index + 1 < self.findings.len() { "," } else { "" })?;
+        }
+        writeln!(f, "]")
+    }
+}
+
+fn mapping_name(page: &PMap) -> String {
+    match &page.mapping_kind {
+        MappingKind::File(file_info) => file_info.full_name().to_string(),
+        mapping_kind => mapping_kind.to_string(),
+    }
+}
+
+const AUDIT_TABLE_SEPARATOR: &str = "|----------|-----------------------------------|--------------|------------|-------------|----------------------------------------------------------|\n";
+
+impl Display for SecurityAudit<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.format == AuditFormat::Json {
+            return self.fmt_json(f);
+        }
+        if self.findings.is_empty() {
+            return writeln!(f, "No findings");
+        }
+        AUDIT_TABLE_SEPARATOR.fmt(f)?;
+        format!("| {:8} | {:33} | {:^12} | {:10} | {:11} | {:56} |\n", "Severity", "Check", "Address", "Size [KiB]", "Permissions", "Mapping").fmt(f)?;
+        AUDIT_TABLE_SEPARATOR.fmt(f)?;
+        for finding in &self.findings {
+            format!(
+                "| {:8} | {:33} | {:12x} | {:10} | {:11} | {:56} |\n",
+                finding.check.severity().to_string(),
+                finding.check.id(),
+                finding.page.address,
+                finding.page.size_in_kibibyte,
+                permissions(finding.page),
+                mapping_name(finding.page)).fmt(f)?;
+        }
+        AUDIT_TABLE_SEPARATOR.fmt(f)?;
+        let mut checks: Vec<AuditCheck> = Vec::new();
+        for finding in &self.findings {
+            if !checks.contains(&finding.check) {
+                checks.push(finding.check);
+            }
+        }
+        for check in checks {
+            writeln!(f, "{}: {}", check.id(), check.explanation())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod security_audit_tests {
+    use super::*;
+    use crate::dotnet_regions::ClassifiedRegion;
+    use crate::file_info::FileInfo;
+
+    #[test]
+    fn findings_of_all_checks() {
+        let anonymous = || MappingKind::AnonymousPrivate(None);
+        let pages = PMapVec(vec![
+            PMap::test_page(0x10000000, "rwxp", 64, anonymous()),
+            PMap::test_page(0x20000000, "r-xp", 4, anonymous()),
+            PMap { virtual_memory_flags: VirtualMemoryFlags::Shared.into(), ..PMap::test_page(0x30000000, "rw-s", 1024, MappingKind::File(FileInfo::new("/var/lib/app/data.bin"))) },
+            PMap { virtual_memory_flags: VirtualMemoryFlags::Shared.into(), ..PMap::test_page(0x31000000, "rw-s", 1024, MappingKind::File(FileInfo::new("/memfd:doublemapper (deleted)"))) },
+            PMap::test_page(0x40000000, "rw-p", 8192, anonymous()),
+            PMap::test_page(0x50000000, "---p", 4, anonymous()),
+            PMap::test_page(0x50001000, "rw-p", 8192, anonymous()),
+            PMap::test_page(0x60000000, "r-xp", 100, MappingKind::File(FileInfo::new("/tmp/libpayload.so"))),
+        ]);
+        let index = pages.index();
+
+        let audit = SecurityAudit::audit(&index, &ClassifiedRegionVec(vec![]), 8192, AuditFormat::Json);
+        let findings = audit.findings.iter().map(|finding| (finding.check, finding.page.address)).collect::<Vec<_>>();
+        assert_eq!(findings, vec![
+            (AuditCheck::WritableExecutable, 0x10000000),
+            (AuditCheck::ExecutableFromWritableLocation, 0x60000000),
+            (AuditCheck::AnonymousExecutable, 0x20000000),
+            (AuditCheck::StackWithoutGuardPage, 0x40000000),
+            (AuditCheck::SharedWritableFile, 0x30000000),
+        ]);
+        assert_eq!(audit.max_severity(), Some(Severity::High));
+        let json = audit.to_string();
+        assert!(json.starts_with("[\n  {\"check\": \"writable-executable\", \"severity\": \"high\", \"address\": \"0x10000000\""));
+        assert!(json.contains("\"mapping\": \"/tmp/libpayload.so\""));
+    }
+
+    #[test]
+    fn exempt_only_jit_regions_from_anonymous_executable() {
+        let region = |address, kind| ClassifiedRegion { address, size_in_kibibyte: 4, resident_set_size_in_kibibyte: 4, kind, confidence: Confidence::High };
+        let pages = PMapVec(vec![
+            PMap::test_page(0x10000000, "r-xp", 4, MappingKind::AnonymousPrivate(None)),
+            PMap::test_page(0x20000000, "r-xp", 4, MappingKind::AnonymousPrivate(None)),
+            PMap::test_page(0x30000000, "r-xp", 4, MappingKind::AnonymousPrivate(None)),
+            PMap::test_page(0x40000000, "r-xp", 4, MappingKind::AnonymousPrivate(None)),
+        ]);
+        let index = pages.index();
+        let classified_regions = ClassifiedRegionVec(vec![
+            region(0x10000000, DotnetRegionKind::ExecutableAllocator),
+            region(0x20000000, DotnetRegionKind::LoaderHeap),
+            region(0x30000000, DotnetRegionKind::GcHeap),
+            region(0x40000000, DotnetRegionKind::ThreadStack),
+        ]);
+
+        let audit = SecurityAudit::audit(&index, &classified_regions, 8192, AuditFormat::Json);
+        let findings = audit.findings.iter().map(|finding| (finding.check, finding.page.address)).collect::<Vec<_>>();
+        assert_eq!(findings, vec![(AuditCheck::AnonymousExecutable, 0x30000000), (AuditCheck::AnonymousExecutable, 0x40000000)]);
+    }
+
+    #[test]
+    fn report_lone_executable_page_of_classified_memory() {
+        let reservation = |address, size_in_kibibyte| PMap {
+            virtual_memory_flags: VirtualMemoryFlags::DoNotIncludeInCoreDump.into(),
+            ..PMap::test_page(address, "---p", size_in_kibibyte, MappingKind::AnonymousPrivate(None))
+        };
+        let pages = PMapVec(vec![
+            PMap::test_page(0x10000000, "r-xp", 4, MappingKind::AnonymousPrivate(None)),
+            // code committed at the start of a reservation of the executable allocator
+            PMap::test_page(0x20000000, "r-xp", 64, MappingKind::AnonymousPrivate(None)),
+            reservation(0x20010000, 960),
+        ]);
+        let index = pages.index();
+        let classified_regions = ClassifiedRegionVec::classify(&pages, 8192);
+
+        let audit = SecurityAudit::audit(&index, &classified_regions, 8192, AuditFormat::Json);
+        let findings = audit.findings.iter().map(|finding| (finding.check, finding.page.address)).collect::<Vec<_>>();
+        assert_eq!(findings, vec![(AuditCheck::AnonymousExecutable, 0x10000000)]);
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::pmap::*;
+use crate::pmap_analyzer::{CategorySortKey, PMapCategory, PMapCategoryVec};
+
+/// How a memory page changed between two snapshots of the same process
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ChangeKind {
+    New,
+    Removed,
+    Grown,
+    Shrunk,
+}
+
+impl Display for ChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChangeKind::New => "New".fmt(f),
+            ChangeKind::Removed => "Removed".fmt(f),
+            ChangeKind::Grown => "Grown".fmt(f),
+            ChangeKind::Shrunk => "Shrunk".fmt(f),
+        }
+    }
+}
+
+fn delta(before: u64, after: u64) -> i64 {
+    after as i64 - before as i64
+}
+
+/// Memory page that is new, removed or changed in size or memory usage
+#[derive(Debug, PartialEq)]
+pub struct MappingChange<'a> {
+    pub kind: ChangeKind,
+    pub before: Option<&'a PMap>,
+    pub after: Option<&'a PMap>,
+}
+
+impl MappingChange<'_> {
+    fn page(&self) -> &PMap {
+        self.after.or(self.before).unwrap()
+    }
+
+    /// Difference of a column between both snapshots, a missing page counts as 0
+    pub fn delta(&self, column: fn(&PMap) -> u64) -> i64 {
+        delta(self.before.map(column).unwrap_or(0), self.after.map(column).unwrap_or(0))
+    }
+
+    // biggest absolute change of virtual size and resident memory
+    fn magnitude(&self) -> u64 {
+        self.delta(|page| page.size_in_kibibyte).unsigned_abs().max(self.delta(|page| page.resident_set_size_in_kibibyte).unsigned_abs())
+    }
+}
+
+/// Category with its aggregates in both snapshots
+#[derive(Debug, PartialEq)]
+pub struct CategoryDelta<'a> {
+    pub name: String,
+    pub before: Option<&'a PMapCategory>,
+    pub after: Option<&'a PMapCategory>,
+}
+
+impl CategoryDelta<'_> {
+    pub fn value_before(&self, key: CategorySortKey) -> u64 {
+        self.before.map(|category| category.value_of(key)).unwrap_or(0)
+    }
+
+    pub fn value_after(&self, key: CategorySortKey) -> u64 {
+        self.after.map(|category| category.value_of(key)).unwrap_or(0)
+    }
+
+    pub fn delta(&self, key: CategorySortKey) -> i64 {
+        delta(self.value_before(key), self.value_after(key))
+    }
+}
+
+/// Differences between two snapshots of the same process
+pub struct SnapshotDiff<'a> {
+    // ordered by the biggest change
+    pub changes: Vec<MappingChange<'a>>,
+    // ordered by the biggest change of the sort key
+    pub categories: Vec<CategoryDelta<'a>>,
+    // number of memory page changes to show
+    pub top: usize,
+}
+
+impl<'a> SnapshotDiff<'a> {
+    /// Matches the memory pages of both snapshots by address and backing file (or kind of anonymous memory)
+    pub fn compare(
+        before: &'a PMapVec,
+        after: &'a PMapVec,
+        before_categories: &'a PMapCategoryVec,
+        after_categories: &'a PMapCategoryVec,
+        sort_by: CategorySortKey,
+        top: usize)
+        -> Self {
+        let mut before_by_address: HashMap<u64, &PMap> = before.0.iter().map(|page| (page.address, page)).collect();
+        let mut changes = Vec::new();
+        for page in &after.0 {
+            match before_by_address.get(&page.address) {
+                Some(old_page) if old_page.mapping_kind == page.mapping_kind => {
+                    let change = MappingChange { kind: ChangeKind::Grown, before: Some(*old_page), after: Some(page) };
+                    before_by_address.remove(&page.address);
+                    let primary = [
+                        change.delta(|page| page.size_in_kibibyte),
+                        change.delta(|page| page.resident_set_size_in_kibibyte),
+                        change.delta(|page| page.proportional_share_size_in_kibibyte),
+                        change.delta(|page| page.private_dirty_in_kibibyte),
+                    ].into_iter().find(|delta| *delta != 0);
+                    match primary {
+                        Some(delta) if delta > 0 => changes.push(change),
+                        Some(_) => changes.push(MappingChange { kind: ChangeKind::Shrunk, ..change }),
+                        None => {}
+                    }
+                }
+                _ => changes.push(MappingChange { kind: ChangeKind::New, before: None, after: Some(page) }),
+            }
+        }
+        changes.extend(before_by_address.into_values().map(|page| MappingChange { kind: ChangeKind::Removed, before: Some(page), after: None }));
+        changes.sort_by_key(|change| (std::cmp::Reverse(change.magnitude()), change.page().address));
+
+        let mut categories: Vec<CategoryDelta> = before_categories.0
+            .iter()
+            .map(|category| CategoryDelta {
+                name: category.name.clone(),
+                before: Some(category),
+                after: after_categories.0.iter().find(|new_category| new_category.name == category.name),
+            })
+            .collect();
+        categories.extend(after_categories.0
+            .iter()
+            .filter(|category| !before_categories.0.iter().any(|old_category| old_category.name == category.name))
+            .map(|category| CategoryDelta { name: category.name.clone(), before: None, after: Some(category) }));
+        categories.sort_by_key(|category| std::cmp::Reverse(category.delta(sort_by).unsigned_abs()));
+
+        SnapshotDiff { changes, categories, top }
+    }
+}
+
+const CATEGORY_DIFF_TABLE_SEPARATOR: &str = "|----------------------------------------------------------|------------|------------|------------|------------|------------|------------|---------------|---------------|-----------------|-----------------|\n";
+const MAPPING_DIFF_TABLE_SEPARATOR: &str = "|----------|--------------|--------------------------------|------------|------------|------------|------------|------------|------------|---------------|---------------|\n";
+
+impl Display for SnapshotDiff<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let keys = [CategorySortKey::Size, CategorySortKey::Rss, CategorySortKey::Pss, CategorySortKey::PrivateDirty, CategorySortKey::Pages];
+        CATEGORY_DIFF_TABLE_SEPARATOR.fmt(f)?;
+        format!(
+            "| {:56} | {:10} | {:10} | {:10} | {:10} | {:10} | {:10} | {:13} | {:13} | {:15} | {:15} |\n",
+            "Category", "Size [KiB]", "Delta Size", "RSS [KiB]", "Delta RSS", "PSS [KiB]", "Delta PSS", "Private Dirty", "Delta P.Dirty", "#Memory Pages", "Delta #Pages").fmt(f)?;
+        CATEGORY_DIFF_TABLE_SEPARATOR.fmt(f)?;
+        let mut totals = [(0u64, 0i64); 5];
+        for category in &self.categories {
+            let [size, rss, pss, private_dirty, pages] = keys.map(|key| (category.value_after(key), category.delta(key)));
+            format!(
+                "| {:56} | {:10} | {:+10} | {:10} | {:+10} | {:10} | {:+10} | {:13} | {:+13} | {:15} | {:+15} |\n",
+                category.name, size.0, size.1, rss.0, rss.1, pss.0, pss.1, private_dirty.0, private_dirty.1, pages.0, pages.1).fmt(f)?;
+            for (total, key) in totals.iter_mut().zip(keys) {
+                total.0 += category.value_after(key);
+                total.1 += category.delta(key);
+            }
+        }
+        CATEGORY_DIFF_TABLE_SEPARATOR.fmt(f)?;
+        let [size, rss, pss, private_dirty, pages] = totals;
+        format!(
+            "| {:56} | {:10} | {:+10} | {:10} | {:+10} | {:10} | {:+10} | {:13} | {:+13} | {:15} | {:+15} |\n",
+            "", size.0, size.1, rss.0, rss.1, pss.0, pss.1, private_dirty.0, private_dirty.1, pages.0, pages.1).fmt(f)?;
+        CATEGORY_DIFF_TABLE_SEPARATOR.fmt(f)?;
+        writeln!(f)?;
+
+        for kind in [ChangeKind::New, ChangeKind::Removed, ChangeKind::Grown, ChangeKind::Shrunk] {
+            let changes = self.changes.iter().filter(|change| change.kind == kind);
+            write!(f, "{}: {}, ", kind, changes.count())?;
+        }
+        writeln!(f, "biggest {} changes of memory pages:", self.top.min(self.changes.len()))?;
+        MAPPING_DIFF_TABLE_SEPARATOR.fmt(f)?;
+        format!(
+            "| {:8} | {:^12} | {:30} | {:10} | {:10} | {:10} | {:10} | {:10} | {:10} | {:13} | {:13} |\n",
+            "Change", "Address", "Mapping Kind", "Size [KiB]", "Delta Size", "RSS [KiB]", "Delta RSS", "PSS [KiB]", "Delta PSS", "Private Dirty", "Delta P.Dirty").fmt(f)?;
+        MAPPING_DIFF_TABLE_SEPARATOR.fmt(f)?;
+        for change in self.changes.iter().take(self.top) {
+            let after = |column: fn(&PMap) -> u64| change.after.map(column).unwrap_or(0);
+            format!(
+                "| {:8} | {:12x} | {:30} | {:10} | {:+10} | {:10} | {:+10} | {:10} | {:+10} | {:13} | {:+13} |\n",
+                change.kind.to_string(),
+                change.page().address,
+                change.page().mapping_kind.to_string(),
+                after(|page| page.size_in_kibibyte),
+                change.delta(|page| page.size_in_kibibyte),
+                after(|page| page.resident_set_size_in_kibibyte),
+                change.delta(|page| page.resident_set_size_in_kibibyte),
+                after(|page| page.proportional_share_size_in_kibibyte),
+                change.delta(|page| page.proportional_share_size_in_kibibyte),
+                after(|page| page.private_dirty_in_kibibyte),
+                change.delta(|page| page.private_dirty_in_kibibyte)).fmt(f)?;
+        }
+        MAPPING_DIFF_TABLE_SEPARATOR.fmt(f)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod snapshot_diff_tests {
+    use super::*;
+
+    fn page(address: u64, mapping_kind: MappingKind, size_in_kibibyte: u64, resident_set_size_in_kibibyte: u64) -> PMap {
+        PMap {
+            address,
+            size_in_kibibyte,
+            resident_set_size_in_kibibyte,
+            proportional_share_size_in_kibibyte: resident_set_size_in_kibibyte,
+            mapping_kind,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn match_pages_by_address_and_backing() {
+        let before = PMapVec(vec![
+            page(0x1000, MappingKind::Heap, 100, 50),
+            page(0x2000, MappingKind::AnonymousPrivate(None), 64, 64),
+            page(0x3000, MappingKind::Stack, 8, 8),
+            page(0x4000, MappingKind::AnonymousPrivate(None), 4, 4),
+        ]);
+        let after = PMapVec(vec![
+            page(0x1000, MappingKind::Heap, 300, 250),
+            page(0x2000, MappingKind::AnonymousPrivate(None), 64, 10),
+            page(0x3000, MappingKind::Stack, 8, 8),
+            page(0x4000, MappingKind::VirtualDynamicSharedObject, 4, 4),
+        ]);
+        let categorize = |pages: &PMapVec| PMapCategory::get_categories_from_memory_pages(pages.clone(), &|_| "Anonymous".to_string(), CategorySortKey::Pss).unwrap();
+        let (before_categories, after_categories) = (categorize(&before), categorize(&after));
+
+        let diff = SnapshotDiff::compare(&before, &after, &before_categories, &after_categories, CategorySortKey::Pss, 10);
+        let kinds = diff.changes.iter().map(|change| (change.kind, change.page().address)).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![(ChangeKind::Grown, 0x1000), (ChangeKind::Shrunk, 0x2000), (ChangeKind::New, 0x4000), (ChangeKind::Removed, 0x4000)]);
+        assert_eq!(diff.changes[0].delta(|page| page.size_in_kibibyte), 200);
+
+        let categories = diff.categories.iter().map(|category| (category.name.as_str(), category.delta(CategorySortKey::Pss))).collect::<Vec<_>>();
+        assert_eq!(categories, vec![("[heap]", 200), ("Anonymous", -58), ("[vdso]", 4), ("[stack]", 0)]);
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt::Display;
+
+use crate::dotnet_regions::{ClassifiedRegionVec, DotnetRegionKind};
+use crate::pmap::*;
+use crate::pmap_analyzer::PMapCategoryVec;
+
+// categories with at least this share of their memory swapped out are flagged
+const SIGNIFICANT_SWAP_SHARE_IN_PERCENT: f64 = 10.0;
+// and at least this much swapped out, to ignore tiny categories
+const SIGNIFICANT_SWAP_IN_KIBIBYTE: u64 = 1024;
+// swap-in times (in seconds) of the memory a full GC touches, from which on the stall risk is medium or high
+const MEDIUM_STALL_IN_SECONDS: f64 = 0.1;
+const HIGH_STALL_IN_SECONDS: f64 = 1.0;
+
+/// Resident and swapped memory of a category or region kind
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SwapUsage {
+    pub name: String,
+    pub resident_set_size_in_kibibyte: u64,
+    pub swap_in_kibibyte: u64,
+    pub swap_pss_in_kibibyte: u64,
+}
+
+impl SwapUsage {
+    fn add(&mut self, page: &PMap) {
+        self.resident_set_size_in_kibibyte += page.resident_set_size_in_kibibyte;
+        self.swap_in_kibibyte += page.swap_in_kibibyte;
+        self.swap_pss_in_kibibyte += page.swap_pss_in_kibibyte;
+    }
+
+    /// Share of the memory (resident and swapped) that is swapped out
+    pub fn swapped_in_percent(&self) -> f64 {
+        let total = self.resident_set_size_in_kibibyte + self.swap_in_kibibyte;
+        if total == 0 {
+            0.0
+        } else {
+            100.0 * self.swap_in_kibibyte as f64 / total as f64
+        }
+    }
+
+    pub fn is_significant(&self) -> bool {
+        self.swap_in_kibibyte >= SIGNIFICANT_SWAP_IN_KIBIBYTE && self.swapped_in_percent() >= SIGNIFICANT_SWAP_SHARE_IN_PERCENT
+    }
+}
+
+/// How likely a full GC stalls on reading swapped out memory
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StallRisk {
+    None,
+    Low,
+    Medium,
+    High,
+}
+
+impl Display for StallRisk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StallRisk::None => "None".fmt(f),
+            StallRisk::Low => "Low".fmt(f),
+            StallRisk::Medium => "Medium".fmt(f),
+            StallRisk::High => "High".fmt(f),
+        }
+    }
+}
+
+/// Swapped memory per category and per kind of .NET region
+pub struct SwapAnalysis {
+    // categories with swapped memory ordered by swap
+    pub categories: Vec<SwapUsage>,
+    // .NET region kinds with swapped memory ordered by swap
+    pub regions: Vec<SwapUsage>,
+    // GC heap and bookkeeping, which are touched by a full (gen2) GC
+    pub gc_scanned: SwapUsage,
+    // assumed read throughput of the swap device
+    pub swap_in_throughput_in_mebibyte_per_second: u64,
+}
+
+impl SwapAnalysis {
+    pub fn analyze(categories: &PMapCategoryVec, classified_regions: &ClassifiedRegionVec, swap_in_throughput_in_mebibyte_per_second: u64) -> Self {
+        let mut category_usages = categories.0
+            .iter()
+            .map(|category| {
+                let mut usage = SwapUsage { name: category.name.clone(), ..Default::default() };
+                category.pages.iter().for_each(|page| usage.add(page));
+                usage
+            })
+            .filter(|usage| usage.swap_in_kibibyte > 0)
+            .collect::<Vec<_>>();
+        category_usages.sort_by_key(|usage| std::cmp::Reverse(usage.swap_in_kibibyte));
+
+        let mut region_usages: Vec<(DotnetRegionKind, SwapUsage)> = Vec::new();
+        let mut gc_scanned = SwapUsage { name: "GC Heap and Bookkeeping".to_string(), ..Default::default() };
+        for page in categories.0.iter().flat_map(|category| category.pages.iter()) {
+            let Some(region) = classified_regions.find(page.address) else {
+                continue;
+            };
+            match region_usages.iter_mut().find(|(kind, _)| *kind == region.kind) {
+                Some((_, usage)) => usage.add(page),
+                None => {
+                    let mut usage = SwapUsage { name: region.kind.to_string(), ..Default::default() };
+                    usage.add(page);
+                    region_usages.push((region.kind, usage));
+                }
+            }
+            if matches!(region.kind, DotnetRegionKind::GcHeap | DotnetRegionKind::GcBookkeeping) {
+                gc_scanned.add(page);
+            }
+        }
+        let mut region_usages = region_usages
+            .into_iter()
+            .map(|(_, usage)| usage)
+            .filter(|usage| usage.swap_in_kibibyte > 0)
+            .collect::<Vec<_>>();
+        region_usages.sort_by_key(|usage| std::cmp::Reverse(usage.swap_in_kibibyte));
+
+        SwapAnalysis { categories: category_usages, regions: region_usages, gc_scanned, swap_in_throughput_in_mebibyte_per_second }
+    }
+
+    pub fn swap_in_kibibyte(&self) -> u64 {
+        self.categories.iter().map(|usage| usage.swap_in_kibibyte).sum()
+    }
+
+    /// Time to read the swapped out GC heap back in, which a full GC has to wait for
+    pub fn full_gc_swap_in_seconds(&self) -> f64 {
+        self.gc_scanned.swap_in_kibibyte as f64 / 1024.0 / self.swap_in_throughput_in_mebibyte_per_second.max(1) as f64
+    }
+
+    pub fn full_gc_stall_risk(&self) -> StallRisk {
+        let seconds = self.full_gc_swap_in_seconds();
+        if self.gc_scanned.swap_in_kibibyte == 0 {
+            StallRisk::None
+        } else if seconds >= HIGH_STALL_IN_SECONDS {
+            StallRisk::High
+        } else if seconds >= MEDIUM_STALL_IN_SECONDS {
+            StallRisk::Medium
+        } else {
+            StallRisk::Low
+        }
+    }
+}
+
+const SWAP_TABLE_SEPARATOR: &str = "|----------------------------------------------------------|------------|------------|---------------|-------------|-------------|\n";
+
+fn fmt_usages(f: &mut std::fmt::Formatter<'_>, title: &str, usages: &[SwapUsage]) -> std::fmt::Result {
+    SWAP_TABLE_SEPARATOR.fmt(f)?;
+    format!("| {:56} | {:10} | {:10} | {:13} | {:11} | {:11} |\n", title, "RSS [KiB]", "Swap [KiB]", "SwapPss [KiB]", "Swapped [%]", "Significant").fmt(f)?;
+    SWAP_TABLE_SEPARATOR.fmt(f)?;
+    for usage in usages {
+        format!(
+            "| {:56} | {:10} | {:10} | {:13} | {:11.1} | {:11} |\n",
+            usage.name,
+            usage.resident_set_size_in_kibibyte,
+            usage.swap_in_kibibyte,
+            usage.swap_pss_in_kibibyte,
+            usage.swapped_in_percent(),
+            if usage.is_significant() { "yes" } else { "" }).fmt(f)?;
+    }
+    SWAP_TABLE_SEPARATOR.fmt(f)
+}
+
+impl Display for SwapAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.categories.is_empty() {
+            return writeln!(f, "No memory is swapped out");
+        }
+        writeln!(f, "Swapped out: {} KiB", self.swap_in_kibibyte())?;
+        fmt_usages(f, "Category", &self.categories)?;
+        if !self.regions.is_empty() {
+            fmt_usages(f, ".NET Region", &self.regions)?;
+        }
+        writeln!(
+            f,
+            "Full GC Stall Risk: {} ({} KiB of the GC heap and bookkeeping swapped out, about {:.2} s to swap in at {} MiB/s)",
+            self.full_gc_stall_risk(),
+            self.gc_scanned.swap_in_kibibyte,
+            self.full_gc_swap_in_seconds(),
+            self.swap_in_throughput_in_mebibyte_per_second)?;
+        if self.full_gc_stall_risk() >= StallRisk::Medium {
+            writeln!(f, "A gen2 GC marks and sweeps the whole GC heap and has to wait for every swapped out page, native caches are only read when used")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod swap_tests {
+    use super::*;
+    use crate::dotnet_regions::{ClassifiedRegion, Confidence};
+    use crate::pmap_analyzer::{CategorySortKey, PMapCategory};
+
+    #[test]
+    fn swapped_gc_heap_and_native_memory() {
+        let page = |address: u64, resident_set_size_in_kibibyte: u64, swap_in_kibibyte: u64| PMap {
+            address,
+            size_in_kibibyte: resident_set_size_in_kibibyte + swap_in_kibibyte,
+            resident_set_size_in_kibibyte,
+            swap_in_kibibyte,
+            swap_pss_in_kibibyte: swap_in_kibibyte,
+            mapping_kind: MappingKind::AnonymousPrivate(None),
+            ..Default::default()
+        };
+        let pages = PMapVec(vec![page(0x10000000, 600 * 1024, 400 * 1024), page(0x80000000, 1024, 64), page(0x90000000, 4096, 0)]);
+        let classified_regions = ClassifiedRegionVec(vec![ClassifiedRegion {
+            address: 0x10000000,
+            size_in_kibibyte: 1000 * 1024,
+            resident_set_size_in_kibibyte: 600 * 1024,
+            kind: DotnetRegionKind::GcHeap,
+            confidence: Confidence::High,
+        }]);
+        let categories = PMapCategory::get_categories_from_memory_pages(
+            pages,
+            &|page| if page.address == 0x10000000 { DotnetRegionKind::GcHeap.to_string() } else { "Anonymous".to_string() },
+            CategorySortKey::Pss).unwrap();
+
+        let analysis = SwapAnalysis::analyze(&categories, &classified_regions, 100);
+        assert_eq!(analysis.swap_in_kibibyte(), 400 * 1024 + 64);
+        assert_eq!(analysis.categories[0].swapped_in_percent(), 40.0);
+        assert!(analysis.categories[0].is_significant());
+        assert!(!analysis.categories[1].is_significant());
+        assert_eq!(analysis.regions.len(), 1);
+        assert_eq!(analysis.full_gc_swap_in_seconds(), 4.0);
+        assert_eq!(analysis.full_gc_stall_risk(), StallRisk::High);
+    }
+}
@@ -0,0 +1,334 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::pmap::*;
+use crate::pmap_analyzer::PMapCategoryVec;
+
+// A snapshot is taken at the time found in its file name, e.g.
+// pmap_20240105T134500, pmap-2024-01-05_13-45-00.txt, pmap.1704462300
+// and at the modification time of the file otherwise.
+
+// minimum number of snapshots to judge whether a category grows steadily
+const MIN_SNAPSHOTS_FOR_LEAK_DETECTION: usize = 3;
+
+/// Seconds since the unix epoch of a date (proleptic gregorian calendar, UTC)
+fn unix_time(year: u64, month: u64, day: u64, hour: u64, minute: u64, second: u64) -> Option<u64> {
+    if !(1970..10000).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    // days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146097 + day_of_era).checked_sub(719468)?;
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Time (seconds since the unix epoch) written in a file name as `YYYYMMDDhhmmss`, `YYYYMMDD_hhmmss`,
+/// `YYYY-MM-DD_hh-mm-ss` (any separators) or as unix time with 10 digits
+pub fn timestamp_from_name(name: &str) -> Option<u64> {
+    let runs = name.split(|c: char| !c.is_ascii_digit()).filter(|run| !run.is_empty()).collect::<Vec<_>>();
+    let number = |run: &str, range: std::ops::Range<usize>| run[range].parse::<u64>().unwrap_or(0);
+    let lengths = runs.iter().map(|run| run.len()).collect::<Vec<_>>();
+    for index in 0..runs.len() {
+        let run = runs[index];
+        let timestamp = match &lengths[index..] {
+            [14, ..] => unix_time(number(run, 0..4), number(run, 4..6), number(run, 6..8), number(run, 8..10), number(run, 10..12), number(run, 12..14)),
+            [8, 6, ..] => {
+                let time = runs[index + 1];
+                unix_time(number(run, 0..4), number(run, 4..6), number(run, 6..8), number(time, 0..2), number(time, 2..4), number(time, 4..6))
+            }
+            [4, 2, 2, 2, 2, 2, ..] => {
+                let parts = runs[index..index + 6].iter().map(|part| part.parse::<u64>().unwrap_or(0)).collect::<Vec<_>>();
+                unix_time(parts[0], parts[1], parts[2], parts[3], parts[4], parts[5])
+            }
+            [10, ..] => run.parse().ok(),
+            _ => None,
+        };
+        if timestamp.is_some() {
+            return timestamp;
+        }
+    }
+    None
+}
+
+fn timestamp_of(path: &Path) -> Result<u64, Box<dyn Error>> {
+    if let Some(timestamp) = path.file_name().and_then(|name| name.to_str()).and_then(timestamp_from_name) {
+        return Ok(timestamp);
+    }
+    Ok(path.metadata()?.modified()?.duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+/// Returns true if the file starts like a pmap output (`<PID>:   <command line>`)
+fn is_pmap_output(path: &Path) -> bool {
+    let mut first_line = String::new();
+    let read = std::fs::File::open(path).and_then(|file| std::io::BufRead::read_line(&mut std::io::BufReader::new(file), &mut first_line));
+    read.is_ok() && first_line.split_once(':').is_some_and(|(pid, _)| pid.trim().parse::<u32>().is_ok())
+}
+
+/// Snapshot files with their timestamps ordered by time, directories are replaced by the pmap outputs inside of them
+/// (other files like a README are skipped)
+pub fn collect_snapshots(paths: &[String]) -> Result<Vec<(String, u64)>, Box<dyn Error>> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for path in paths.iter().map(PathBuf::from) {
+        if path.is_dir() {
+            let mut entries = std::fs::read_dir(&path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            entries.retain(|entry| entry.is_file() && is_pmap_output(entry));
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(path);
+        }
+    }
+    let mut snapshots = files
+        .iter()
+        .map(|file| Ok((std::fs::canonicalize(file)?.to_string_lossy().to_string(), timestamp_of(file)?)))
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    snapshots.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+    // the pmap output given with -p may be part of the folder too (spelled differently, hence the canonical paths)
+    snapshots.dedup();
+    Ok(snapshots)
+}
+
+/// Memory of a category at the time of one snapshot
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Sample {
+    // size of the accessible pages (reserved address ranges without permissions are left out)
+    pub committed_in_kibibyte: u64,
+    pub resident_set_size_in_kibibyte: u64,
+    pub proportional_share_size_in_kibibyte: u64,
+}
+
+/// Growth of one category over all snapshots
+#[derive(Debug, PartialEq)]
+pub struct CategoryTrend {
+    pub name: String,
+    // one sample per snapshot, 0 if the category is missing in the snapshot
+    pub samples: Vec<Sample>,
+    // least squares slopes in KiB per hour
+    pub committed_per_hour: f64,
+    pub resident_set_size_per_hour: f64,
+    pub proportional_share_size_per_hour: f64,
+    // RSS never went down and rose in most of the intervals
+    pub leak_suspect: bool,
+}
+
+impl CategoryTrend {
+    fn new(name: String, samples: Vec<Sample>, hours: &[f64]) -> Self {
+        let series = |value: fn(&Sample) -> u64| samples.iter().map(value).collect::<Vec<_>>();
+        let rss = series(|sample| sample.resident_set_size_in_kibibyte);
+        let increases = rss.windows(2).filter(|pair| pair[1] > pair[0]).count();
+        let decreases = rss.windows(2).filter(|pair| pair[1] < pair[0]).count();
+        CategoryTrend {
+            committed_per_hour: slope(hours, &series(|sample| sample.committed_in_kibibyte)),
+            resident_set_size_per_hour: slope(hours, &rss),
+            proportional_share_size_per_hour: slope(hours, &series(|sample| sample.proportional_share_size_in_kibibyte)),
+            leak_suspect: samples.len() >= MIN_SNAPSHOTS_FOR_LEAK_DETECTION && decreases == 0 && increases * 2 >= rss.len() - 1,
+            name,
+            samples,
+        }
+    }
+
+    pub fn last(&self) -> Sample {
+        self.samples.last().copied().unwrap_or_default()
+    }
+}
+
+// least squares slope of the values over the hours
+fn slope(hours: &[f64], values: &[u64]) -> f64 {
+    let count = hours.len() as f64;
+    let mean_hours = hours.iter().sum::<f64>() / count;
+    let mean_value = values.iter().map(|value| *value as f64).sum::<f64>() / count;
+    let covariance: f64 = hours.iter().zip(values).map(|(hour, value)| (hour - mean_hours) * (*value as f64 - mean_value)).sum();
+    let variance: f64 = hours.iter().map(|hour| (hour - mean_hours).powi(2)).sum();
+    if variance == 0.0 { 0.0 } else { covariance / variance }
+}
+
+/// Categories of a series of snapshots of the same process
+pub struct TimeSeries {
+    // path and timestamp of each snapshot
+    pub snapshots: Vec<(String, u64)>,
+    // ordered by the RSS growth
+    pub trends: Vec<CategoryTrend>,
+    pub total: CategoryTrend,
+    pub memory_limit_in_kibibyte: Option<u64>,
+}
+
+impl TimeSeries {
+    /// Builds the time series from the categories of each snapshot, the snapshots have to be ordered by time
+    pub fn analyze(snapshots: Vec<(String, u64, PMapCategoryVec)>, memory_limit_in_kibibyte: Option<u64>) -> Self {
+        let first = snapshots.first().map(|(_, timestamp, _)| *timestamp).unwrap_or(0);
+        let hours = snapshots.iter().map(|(_, timestamp, _)| (timestamp - first) as f64 / 3600.0).collect::<Vec<_>>();
+
+        let mut names: Vec<String> = Vec::new();
+        for (_, _, categories) in &snapshots {
+            for category in &categories.0 {
+                if !names.contains(&category.name) {
+                    names.push(category.name.clone());
+                }
+            }
+        }
+        let sample = |categories: &PMapCategoryVec, name: &str| {
+            categories.0.iter().find(|category| category.name == name).map(|category| Sample {
+                committed_in_kibibyte: category.pages
+                    .iter()
+                    .filter(|page| page.permissions.contains(Permissions::Read))
+                    .map(|page| page.size_in_kibibyte)
+                    .sum(),
+                resident_set_size_in_kibibyte: category.resident_set_size_in_kibibyte,
+                proportional_share_size_in_kibibyte: category.proportional_share_size_in_kibibyte,
+            }).unwrap_or_default()
+        };
+
+        let mut trends = names
+            .into_iter()
+            .map(|name| {
+                let samples = snapshots.iter().map(|(_, _, categories)| sample(categories, &name)).collect();
+                CategoryTrend::new(name, samples, &hours)
+            })
+            .collect::<Vec<_>>();
+        trends.sort_by(|a, b| b.resident_set_size_per_hour.total_cmp(&a.resident_set_size_per_hour));
+
+        let total_samples = (0..snapshots.len())
+            .map(|index| trends.iter().fold(Sample::default(), |total, trend| Sample {
+                committed_in_kibibyte: total.committed_in_kibibyte + trend.samples[index].committed_in_kibibyte,
+                resident_set_size_in_kibibyte: total.resident_set_size_in_kibibyte + trend.samples[index].resident_set_size_in_kibibyte,
+                proportional_share_size_in_kibibyte: total.proportional_share_size_in_kibibyte + trend.samples[index].proportional_share_size_in_kibibyte,
+            }))
+            .collect();
+        TimeSeries {
+            snapshots: snapshots.into_iter().map(|(path, timestamp, _)| (path, timestamp)).collect(),
+            total: CategoryTrend::new("Total".to_string(), total_samples, &hours),
+            trends,
+            memory_limit_in_kibibyte,
+        }
+    }
+
+    /// Hours until the RSS of the process reaches the memory limit at the current growth rate,
+    /// None without limit or growth
+    pub fn hours_until_limit(&self) -> Option<f64> {
+        let limit = self.memory_limit_in_kibibyte?;
+        let current = self.total.last().resident_set_size_in_kibibyte;
+        if current >= limit {
+            Some(0.0)
+        } else if self.total.resident_set_size_per_hour > 0.0 {
+            Some((limit - current) as f64 / self.total.resident_set_size_per_hour)
+        } else {
+            None
+        }
+    }
+}
+
+const TREND_TABLE_SEPARATOR: &str = "|----------------------------------------------------------|-----------------|-------------------|------------|-------------------|------------|-------------------|--------------|\n";
+
+impl Display for TimeSeries {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let first = self.snapshots.first().map(|(_, timestamp)| *timestamp).unwrap_or(0);
+        for (path, timestamp) in &self.snapshots {
+            writeln!(f, "{:+8.2} h  {}", (timestamp - first) as f64 / 3600.0, path)?;
+        }
+        writeln!(f)?;
+
+        TREND_TABLE_SEPARATOR.fmt(f)?;
+        format!(
+            "| {:56} | {:15} | {:17} | {:10} | {:17} | {:10} | {:17} | {:12} |\n",
+            "Category", "Committed [KiB]", "Committed [KiB/h]", "RSS [KiB]", "RSS [KiB/h]", "PSS [KiB]", "PSS [KiB/h]", "Leak Suspect").fmt(f)?;
+        TREND_TABLE_SEPARATOR.fmt(f)?;
+        let row = |trend: &CategoryTrend| {
+            let last = trend.last();
+            format!(
+                "| {:56} | {:15} | {:+17.1} | {:10} | {:+17.1} | {:10} | {:+17.1} | {:12} |\n",
+                trend.name,
+                last.committed_in_kibibyte,
+                trend.committed_per_hour,
+                last.resident_set_size_in_kibibyte,
+                trend.resident_set_size_per_hour,
+                last.proportional_share_size_in_kibibyte,
+                trend.proportional_share_size_per_hour,
+                if trend.leak_suspect { "yes" } else { "" })
+        };
+        for trend in &self.trends {
+            row(trend).fmt(f)?;
+        }
+        TREND_TABLE_SEPARATOR.fmt(f)?;
+        row(&self.total).fmt(f)?;
+        TREND_TABLE_SEPARATOR.fmt(f)?;
+
+        if self.snapshots.len() < MIN_SNAPSHOTS_FOR_LEAK_DETECTION {
+            writeln!(f, "At least {} snapshots are needed to detect leak suspects", MIN_SNAPSHOTS_FOR_LEAK_DETECTION)?;
+        }
+        if let Some(limit) = self.memory_limit_in_kibibyte {
+            match self.hours_until_limit() {
+                Some(0.0) => writeln!(f, "The memory limit of {} KiB is already reached", limit)?,
+                Some(hours) => writeln!(f, "Projected time until the memory limit of {} KiB is reached: {:.1} h", limit, hours)?,
+                None => writeln!(f, "The memory limit of {} KiB is not reached, RSS does not grow", limit)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod time_series_tests {
+    use super::*;
+    use crate::pmap_analyzer::{CategorySortKey, PMapCategory};
+
+    #[test]
+    fn timestamps_from_file_names() {
+        assert_eq!(timestamp_from_name("pmap_20240105T134500"), Some(1704462300));
+        assert_eq!(timestamp_from_name("pmap-5159-20240105_134500.txt"), Some(1704462300));
+        assert_eq!(timestamp_from_name("pmap-2024-01-05_13-45-00"), Some(1704462300));
+        assert_eq!(timestamp_from_name("pmap.1704462300"), Some(1704462300));
+        assert_eq!(timestamp_from_name("pmap_5159"), None);
+    }
+
+    #[test]
+    fn skip_other_files_of_a_snapshot_folder() {
+        let folder = std::env::temp_dir().join(format!("pmap_snapshots_{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(folder.join("pmap_20240105T134500"), "5159:   dotnet demo.dll\n").unwrap();
+        std::fs::write(folder.join("README.md"), "# Soak test\n").unwrap();
+        std::fs::write(folder.join(".gitkeep"), "").unwrap();
+        std::fs::write(folder.join("snapshots.tar.gz"), [0x1f, 0x8b, 0x08, 0x00, 0xff, 0xfe]).unwrap();
+
+        let snapshot = folder.join(".").join("pmap_20240105T134500").to_string_lossy().to_string();
+        let snapshots = collect_snapshots(&[snapshot, folder.to_string_lossy().to_string()]);
+        std::fs::remove_dir_all(&folder).unwrap();
+        let snapshots = snapshots.unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert!(snapshots[0].0.ends_with("pmap_20240105T134500"));
+        assert_eq!(snapshots[0].1, 1704462300);
+    }
+
+    #[test]
+    fn growth_rates_leak_suspects_and_projection() {
+        let snapshot = |hour: u64, heap: u64, stack: u64| {
+            let page = |mapping_kind: MappingKind, rss: u64| PMap {
+                size_in_kibibyte: rss,
+                resident_set_size_in_kibibyte: rss,
+                proportional_share_size_in_kibibyte: rss,
+                mapping_kind,
+                ..Default::default()
+            };
+            let pages = PMapVec(vec![page(MappingKind::Heap, heap), page(MappingKind::Stack, stack)]);
+            let categories = PMapCategory::get_categories_from_memory_pages(pages, &|_| String::new(), CategorySortKey::Pss).unwrap();
+            (format!("pmap_{}", hour), hour * 3600, categories)
+        };
+        let snapshots = vec![snapshot(0, 1000, 100), snapshot(1, 1100, 120), snapshot(2, 1200, 90), snapshot(3, 1300, 100)];
+
+        let time_series = TimeSeries::analyze(snapshots, Some(2000));
+        assert_eq!(time_series.trends[0].name, "[heap]");
+        assert_eq!(time_series.trends[0].resident_set_size_per_hour, 100.0);
+        assert!(time_series.trends[0].leak_suspect);
+        assert!(!time_series.trends[1].leak_suspect);
+        assert_eq!(time_series.total.last().resident_set_size_in_kibibyte, 1400);
+        assert_eq!(time_series.total.resident_set_size_per_hour, 97.0);
+        assert_eq!(time_series.hours_until_limit().map(|hours| hours.round()), Some(6.0));
+    }
+}